@@ -11,7 +11,7 @@ fn gets() -> Option<String> {
     std::io::stdin()
         .read_line(&mut temp)
         .expect("Failed to read line");
-    if temp.trim().len() == 0 {
+    if temp.trim().is_empty() {
         None
     } else {
         Some(temp.trim().to_string())
@@ -0,0 +1,141 @@
+use super::new_runtime;
+use crate::{
+    kv_service::{self, Health, KVAvatar, KVSingleProof},
+    proof_service::{Action, Platform},
+    types::Result,
+    util::crypto::Secp256k1KeyPair,
+};
+use serde_json::Value;
+use std::{
+    ops::{Deref, DerefMut},
+    sync::Arc,
+};
+use tokio::runtime::Runtime;
+
+/// Blocking version of [kv_service::Endpoint].
+pub struct Endpoint {
+    client: kv_service::Client,
+    runtime: Arc<Runtime>,
+}
+
+impl Endpoint {
+    /// Wrap an endpoint with the default transport.
+    /// # Examples
+    /// ```rust,no_run
+    /// # use nextid_sdk::{blocking, kv_service::Endpoint, proof_service::Platform};
+    /// let kv = blocking::kv_service::Endpoint::new(Endpoint::Staging).unwrap();
+    /// let result = kv.find_by_platform_identity(Platform::Twitter, "yeiwb").unwrap();
+    /// ```
+    pub fn new(endpoint: kv_service::Endpoint) -> Result<Self> {
+        Self::from_client(endpoint.into())
+    }
+
+    /// Wrap a [kv_service::Client] (e.g. from [NextIdClient](crate::client::NextIdClient)).
+    pub fn from_client(client: kv_service::Client) -> Result<Self> {
+        Ok(Self {
+            client,
+            runtime: new_runtime()?,
+        })
+    }
+
+    /// See [kv_service::Endpoint::find_by_avatar].
+    pub fn find_by_avatar(&self, avatar: &Secp256k1KeyPair) -> Result<Vec<KVSingleProof>> {
+        self.runtime.block_on(self.client.find_by_avatar(avatar))
+    }
+
+    /// See [kv_service::Endpoint::find_by_platform_identity].
+    pub fn find_by_platform_identity(
+        &self,
+        platform: Platform,
+        identity: &str,
+    ) -> Result<Vec<KVAvatar>> {
+        self.runtime
+            .block_on(self.client.find_by_platform_identity(platform, identity))
+    }
+
+    /// See [kv_service::Endpoint::healthz].
+    pub fn healthz(&self) -> Result<Health> {
+        self.runtime.block_on(self.client.healthz())
+    }
+
+    /// Start a [KVProcedure] on this endpoint, sharing its transport and runtime.
+    pub fn procedure(
+        &self,
+        action: Action,
+        avatar: Secp256k1KeyPair,
+        platform: Platform,
+        identity: &str,
+        patch: Value,
+    ) -> KVProcedure {
+        let mut inner = kv_service::KVProcedure::new(
+            self.client.endpoint.clone(),
+            action,
+            avatar,
+            platform,
+            identity,
+            patch,
+        )
+        .with_transport(self.client.transport.clone());
+        inner.timeout = self.client.timeout;
+        inner.metrics = self.client.metrics.clone();
+        KVProcedure {
+            inner,
+            runtime: self.runtime.clone(),
+        }
+    }
+}
+
+/// Blocking version of [kv_service::KVProcedure].
+/// Fields of the async procedure are reachable through `Deref`.
+pub struct KVProcedure {
+    inner: kv_service::KVProcedure,
+    runtime: Arc<Runtime>,
+}
+
+impl KVProcedure {
+    /// See [kv_service::KVProcedure::new].
+    pub fn new(
+        endpoint: kv_service::Endpoint,
+        action: Action,
+        avatar: Secp256k1KeyPair,
+        platform: Platform,
+        identity: &str,
+        patch: Value,
+    ) -> Result<Self> {
+        Ok(Self {
+            inner: kv_service::KVProcedure::new(
+                endpoint, action, avatar, platform, identity, patch,
+            ),
+            runtime: new_runtime()?,
+        })
+    }
+
+    /// See [kv_service::KVProcedure::get_payload].
+    pub fn get_payload(&mut self) -> Result<()> {
+        self.runtime.block_on(self.inner.get_payload())
+    }
+
+    /// See [kv_service::KVProcedure::submit].
+    pub fn submit(&mut self, avatar_signature: Vec<u8>) -> Result<Vec<KVSingleProof>> {
+        self.runtime.block_on(self.inner.submit(avatar_signature))
+    }
+
+    /// Unwrap the async procedure.
+    pub fn into_inner(self) -> kv_service::KVProcedure {
+        self.inner
+    }
+}
+
+impl Deref for KVProcedure {
+    type Target = kv_service::KVProcedure;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl DerefMut for KVProcedure {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.inner
+    }
+}
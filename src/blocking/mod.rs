@@ -0,0 +1,32 @@
+/// Blocking KVService API.
+pub mod kv_service;
+/// Blocking ProofService API.
+pub mod proof_service;
+#[cfg(test)]
+mod tests;
+
+use crate::types::Result;
+use futures::{stream::BoxStream, StreamExt};
+use std::sync::Arc;
+use tokio::runtime::{Builder, Runtime};
+
+/// Build the runtime shared by a blocking endpoint and the procedures started from it.
+fn new_runtime() -> Result<Arc<Runtime>> {
+    Ok(Arc::new(
+        Builder::new_current_thread().enable_all().build()?,
+    ))
+}
+
+/// Iterator over the items of an async stream, fetching them lazily on the endpoint's runtime.
+pub struct Iter<T> {
+    stream: BoxStream<'static, Result<T>>,
+    runtime: Arc<Runtime>,
+}
+
+impl<T> Iterator for Iter<T> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.runtime.block_on(self.stream.next())
+    }
+}
@@ -0,0 +1,225 @@
+use super::{new_runtime, Iter};
+use crate::{
+    proof_service::{
+        self, Action, Avatar, BatchOptions, Binding, Health, Page, Platform, QueryOptions,
+    },
+    types::Result,
+    util::crypto::Secp256k1KeyPair,
+};
+use futures::StreamExt;
+use std::{
+    collections::HashMap,
+    ops::{Deref, DerefMut},
+    sync::Arc,
+};
+use tokio::runtime::Runtime;
+
+/// Blocking version of [proof_service::Endpoint].
+pub struct Endpoint {
+    client: proof_service::Client,
+    runtime: Arc<Runtime>,
+}
+
+impl Endpoint {
+    /// Wrap an endpoint with the default transport.
+    /// # Examples
+    /// ```rust,no_run
+    /// # use nextid_sdk::{blocking, proof_service::{Endpoint, Platform}};
+    /// let ps = blocking::proof_service::Endpoint::new(Endpoint::Staging).unwrap();
+    /// let avatars = ps.find_by(Platform::Twitter, "yeiwb", false).unwrap();
+    /// ```
+    pub fn new(endpoint: proof_service::Endpoint) -> Result<Self> {
+        Self::from_client(endpoint.into())
+    }
+
+    /// Wrap a [proof_service::Client] (e.g. from [NextIdClient](crate::client::NextIdClient)).
+    pub fn from_client(client: proof_service::Client) -> Result<Self> {
+        Ok(Self {
+            client,
+            runtime: new_runtime()?,
+        })
+    }
+
+    /// See [proof_service::Endpoint::find_by].
+    pub fn find_by(
+        &self,
+        platform: Platform,
+        identity: &str,
+        fetch_all: bool,
+    ) -> Result<Vec<Avatar>> {
+        self.runtime
+            .block_on(self.client.find_by(platform, identity, fetch_all))
+    }
+
+    /// See [proof_service::Endpoint::find_by_avatar].
+    pub fn find_by_avatar(&self, avatar: &Secp256k1KeyPair) -> Result<Option<Avatar>> {
+        self.runtime.block_on(self.client.find_by_avatar(avatar))
+    }
+
+    /// See [proof_service::Endpoint::exists].
+    pub fn exists(
+        &self,
+        platform: Platform,
+        identity: &str,
+        avatar: &Secp256k1KeyPair,
+    ) -> Result<Binding> {
+        self.runtime
+            .block_on(self.client.exists(platform, identity, avatar))
+    }
+
+    /// See [proof_service::Endpoint::find_by_many].
+    pub fn find_by_many<I: AsRef<str>>(
+        &self,
+        platform: Platform,
+        identities: &[I],
+    ) -> Result<HashMap<String, Vec<Avatar>>> {
+        self.runtime
+            .block_on(self.client.find_by_many(platform, identities))
+    }
+
+    /// See [proof_service::Endpoint::find_by_many_with].
+    pub fn find_by_many_with<I: AsRef<str>>(
+        &self,
+        platform: Platform,
+        identities: &[I],
+        options: &BatchOptions,
+    ) -> Result<HashMap<String, Vec<Avatar>>> {
+        self.runtime
+            .block_on(self.client.find_by_many_with(platform, identities, options))
+    }
+
+    /// See [proof_service::Endpoint::find_by_with].
+    pub fn find_by_with(
+        &self,
+        platform: Platform,
+        identity: &str,
+        options: QueryOptions,
+    ) -> Result<Vec<Avatar>> {
+        self.runtime
+            .block_on(self.client.find_by_with(platform, identity, options))
+    }
+
+    /// See [proof_service::Endpoint::find_by_pages].
+    /// Pages are fetched one by one while iterating.
+    pub fn find_by_pages(
+        &self,
+        platform: Platform,
+        identity: &str,
+        options: QueryOptions,
+    ) -> Iter<Page> {
+        Iter {
+            stream: self
+                .client
+                .find_by_pages(platform, identity, options)
+                .boxed(),
+            runtime: self.runtime.clone(),
+        }
+    }
+
+    /// See [proof_service::Endpoint::find_by_stream].
+    /// Pages are fetched one by one while iterating.
+    pub fn find_by_stream(
+        &self,
+        platform: Platform,
+        identity: &str,
+        options: QueryOptions,
+    ) -> Iter<Avatar> {
+        Iter {
+            stream: self
+                .client
+                .find_by_stream(platform, identity, options)
+                .boxed(),
+            runtime: self.runtime.clone(),
+        }
+    }
+
+    /// See [proof_service::Endpoint::healthz].
+    pub fn healthz(&self) -> Result<Health> {
+        self.runtime.block_on(self.client.healthz())
+    }
+
+    /// Start a [ProofProcedure] on this endpoint, sharing its transport and runtime.
+    pub fn procedure(
+        &self,
+        action: Action,
+        avatar: Secp256k1KeyPair,
+        platform: Platform,
+        identity: &str,
+    ) -> ProofProcedure {
+        let mut inner = proof_service::ProofProcedure::new(
+            self.client.endpoint.clone(),
+            action,
+            avatar,
+            platform,
+            identity,
+        )
+        .with_transport(self.client.transport.clone());
+        inner.timeout = self.client.timeout;
+        inner.metrics = self.client.metrics.clone();
+        ProofProcedure {
+            inner,
+            runtime: self.runtime.clone(),
+        }
+    }
+}
+
+/// Blocking version of [proof_service::ProofProcedure].
+/// Fields of the async procedure are reachable through `Deref`.
+pub struct ProofProcedure {
+    inner: proof_service::ProofProcedure,
+    runtime: Arc<Runtime>,
+}
+
+impl ProofProcedure {
+    /// See [proof_service::ProofProcedure::new].
+    pub fn new(
+        endpoint: proof_service::Endpoint,
+        action: Action,
+        avatar: Secp256k1KeyPair,
+        platform: Platform,
+        identity: &str,
+    ) -> Result<Self> {
+        Ok(Self {
+            inner: proof_service::ProofProcedure::new(endpoint, action, avatar, platform, identity),
+            runtime: new_runtime()?,
+        })
+    }
+
+    /// See [proof_service::ProofProcedure::get_payload].
+    pub fn get_payload(&mut self) -> Result<()> {
+        self.runtime.block_on(self.inner.get_payload())
+    }
+
+    /// See [proof_service::ProofProcedure::submit].
+    pub fn submit(
+        &mut self,
+        proof_location: String,
+        avatar_signature: Option<Vec<u8>>,
+        ethereum_signature: Option<Vec<u8>>,
+    ) -> Result<()> {
+        self.runtime.block_on(self.inner.submit(
+            proof_location,
+            avatar_signature,
+            ethereum_signature,
+        ))
+    }
+
+    /// Unwrap the async procedure.
+    pub fn into_inner(self) -> proof_service::ProofProcedure {
+        self.inner
+    }
+}
+
+impl Deref for ProofProcedure {
+    type Target = proof_service::ProofProcedure;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl DerefMut for ProofProcedure {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.inner
+    }
+}
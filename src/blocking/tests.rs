@@ -0,0 +1,61 @@
+use super::proof_service::Endpoint;
+use crate::{
+    proof_service::{self, Platform, QueryOptions},
+    types::Result,
+    util::http::{Transport, TransportRequest, TransportResponse},
+};
+use http::{HeaderMap, StatusCode};
+use std::sync::Arc;
+
+/// Transport answering every query with one avatar.
+struct OneAvatarTransport;
+
+#[async_trait::async_trait]
+impl Transport for OneAvatarTransport {
+    async fn send(&self, request: TransportRequest) -> Result<TransportResponse> {
+        assert_eq!("/v1/proof", request.url.path());
+        let body = r#"{
+            "pagination": {"total": 1, "per": 20, "current": 1, "next": 0},
+            "ids": [{
+                "avatar": "0x03a6a6fc2ae04ac2b6c4ea5c4bf6e92f8b8ab8e4d6cbb7f4e5b43e2ce2e5f3e9c1",
+                "last_arweave_id": "",
+                "proofs": [{
+                    "platform": "twitter",
+                    "identity": "yeiwb",
+                    "created_at": "1662708890",
+                    "last_checked_at": "1662708890",
+                    "is_valid": true,
+                    "invalid_reason": ""
+                }]
+            }]
+        }"#;
+        Ok(TransportResponse {
+            status: StatusCode::OK,
+            headers: HeaderMap::new(),
+            body: body.as_bytes().to_vec(),
+        })
+    }
+}
+
+#[test]
+fn test_blocking_find_by() -> Result<()> {
+    let client = proof_service::Endpoint::Custom("https://example.com".into())
+        .with_transport(Arc::new(OneAvatarTransport));
+    let ps = Endpoint::from_client(client)?;
+    let avatars = ps.find_by(Platform::Twitter, "yeiwb", true)?;
+    assert_eq!(1, avatars.len());
+    assert_eq!("yeiwb", avatars[0].proofs[0].identity);
+    Ok(())
+}
+
+#[test]
+fn test_blocking_find_by_stream() -> Result<()> {
+    let client = proof_service::Endpoint::Custom("https://example.com".into())
+        .with_transport(Arc::new(OneAvatarTransport));
+    let ps = Endpoint::from_client(client)?;
+    let avatars = ps
+        .find_by_stream(Platform::Twitter, "yeiwb", QueryOptions::new())
+        .collect::<Result<Vec<_>>>()?;
+    assert_eq!(1, avatars.len());
+    Ok(())
+}
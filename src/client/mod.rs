@@ -0,0 +1,231 @@
+#[cfg(test)]
+mod tests;
+
+use crate::{
+    kv_service::{self, KVProcedure},
+    proof_service::{self, Action, Platform, ProofProcedure},
+    util::{
+        cache::{CacheConfig, CacheTransport},
+        crypto::Secp256k1KeyPair,
+        failover::{FailoverPolicy, FailoverTransport},
+        http::{HttpConfig, HyperTransport, Transport, DEFAULT_TIMEOUT},
+        metrics::MetricsRecorder,
+        rate_limit::{RateLimitTransport, RateLimits},
+        retry::{RetryPolicy, RetryTransport},
+        singleflight::SingleflightTransport,
+    },
+};
+use serde_json::Value;
+use std::{sync::Arc, time::Duration};
+
+/// Settings of a [NextIdClient].
+#[derive(Debug, Clone)]
+pub struct ClientConfig {
+    /// ProofService server to talk to.
+    pub proof_service: proof_service::Endpoint,
+    /// KVService server to talk to.
+    pub kv_service: kv_service::Endpoint,
+    /// ProofService servers to fall back to when `proof_service` is down, in priority order.
+    pub proof_service_fallbacks: Vec<proof_service::Endpoint>,
+    /// KVService servers to fall back to when `kv_service` is down, in priority order.
+    pub kv_service_fallbacks: Vec<kv_service::Endpoint>,
+    /// How `*_fallbacks` are picked and health-checked.
+    pub failover: FailoverPolicy,
+    /// Connection pool and HTTP/2 settings.
+    pub http: HttpConfig,
+    /// Deadline of each API call, retries included. `None` waits forever.
+    /// Override it per call with `with_timeout()`.
+    pub timeout: Option<Duration>,
+    /// Retry policy of failed requests. Use [RetryPolicy::disabled()] to turn it off.
+    pub retry: RetryPolicy,
+    /// Client-side rate limits, shared by all clones of the client. Unlimited by default.
+    /// Each retry attempt takes a token too.
+    pub rate_limit: RateLimits,
+    /// In-memory cache of query responses, shared by all clones of the client. Disabled by default.
+    /// Writes made through the client (e.g. [ProofProcedure::submit]) drop the entries they affect.
+    pub cache: Option<CacheConfig>,
+    /// Let concurrent identical queries share one in-flight request. Disabled by default.
+    pub singleflight: bool,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self {
+            proof_service: proof_service::Endpoint::Production,
+            kv_service: kv_service::Endpoint::Production,
+            proof_service_fallbacks: vec![],
+            kv_service_fallbacks: vec![],
+            failover: FailoverPolicy::default(),
+            http: HttpConfig::default(),
+            timeout: Some(DEFAULT_TIMEOUT),
+            retry: RetryPolicy::default(),
+            rate_limit: RateLimits::default(),
+            cache: None,
+            singleflight: false,
+        }
+    }
+}
+
+/// Long-lived SDK client.
+/// All ProofService and KVService operations made from it (and from its clones)
+/// share one connection pool, so keep a single instance around instead of
+/// calling [Endpoint](proof_service::Endpoint) methods one by one.
+///
+/// # Examples
+/// ```rust,no_run
+/// # #[tokio::main]
+/// # async fn main() {
+/// # use nextid_sdk::client::{ClientConfig, NextIdClient};
+/// # use nextid_sdk::{kv_service, proof_service::{self, Platform}};
+/// let client = NextIdClient::new(ClientConfig {
+///     proof_service: proof_service::Endpoint::Staging,
+///     kv_service: kv_service::Endpoint::Staging,
+///     ..Default::default()
+/// });
+/// let avatars = client.proof_service().find_by(Platform::Twitter, "yeiwb", true).await.unwrap();
+/// let kvs = client.kv_service().find_by_platform_identity(Platform::Twitter, "yeiwb").await.unwrap();
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct NextIdClient {
+    proof_service: proof_service::Client,
+    kv_service: kv_service::Client,
+}
+
+impl NextIdClient {
+    /// Create a client with its own connection pool.
+    pub fn new(config: ClientConfig) -> Self {
+        let http = Arc::new(HyperTransport::with_config(&config.http));
+        Self::layered(config, http)
+    }
+
+    /// Build the transport stack configured by `config` on top of `http`.
+    fn layered(config: ClientConfig, http: Arc<dyn Transport>) -> Self {
+        let mut transport: Arc<dyn Transport> =
+            Arc::new(RateLimitTransport::new(http, config.rate_limit));
+        if !config.proof_service_fallbacks.is_empty() {
+            let backends = std::iter::once(&config.proof_service)
+                .chain(&config.proof_service_fallbacks)
+                .map(proof_service::Endpoint::base_url)
+                .collect();
+            transport = Arc::new(FailoverTransport::new(
+                transport,
+                backends,
+                config.failover.clone(),
+            ));
+        }
+        if !config.kv_service_fallbacks.is_empty() {
+            let backends = std::iter::once(&config.kv_service)
+                .chain(&config.kv_service_fallbacks)
+                .map(kv_service::Endpoint::base_url)
+                .collect();
+            transport = Arc::new(FailoverTransport::new(
+                transport,
+                backends,
+                config.failover.clone(),
+            ));
+        }
+        let mut transport: Arc<dyn Transport> =
+            Arc::new(RetryTransport::new(transport, config.retry));
+        if config.singleflight {
+            transport = Arc::new(SingleflightTransport::new(transport));
+        }
+        if let Some(cache) = config.cache {
+            transport = Arc::new(CacheTransport::new(transport, cache));
+        }
+        let mut client = Self::with_transport(config.proof_service, config.kv_service, transport);
+        client.proof_service.timeout = config.timeout;
+        client.kv_service.timeout = config.timeout;
+        client
+    }
+
+    /// Create a client sending every request through `transport`.
+    pub fn with_transport(
+        proof_service: proof_service::Endpoint,
+        kv_service: kv_service::Endpoint,
+        transport: Arc<dyn Transport>,
+    ) -> Self {
+        Self {
+            proof_service: proof_service.with_transport(transport.clone()),
+            kv_service: kv_service.with_transport(transport),
+        }
+    }
+
+    /// Report metrics of every API call (procedures included) to `metrics`.
+    /// # Examples
+    /// ```rust
+    /// # use std::sync::Arc;
+    /// # use nextid_sdk::client::NextIdClient;
+    /// # use nextid_sdk::util::metrics::{MetricsRecorder, RequestMetrics};
+    /// struct Noop;
+    /// impl MetricsRecorder for Noop {
+    ///     fn record(&self, _: &RequestMetrics) {}
+    /// }
+    /// let client = NextIdClient::default().with_metrics(Arc::new(Noop));
+    /// ```
+    pub fn with_metrics(mut self, metrics: Arc<dyn MetricsRecorder>) -> Self {
+        self.proof_service.metrics = Some(metrics.clone());
+        self.kv_service.metrics = Some(metrics);
+        self
+    }
+
+    /// ProofService operations.
+    pub fn proof_service(&self) -> &proof_service::Client {
+        &self.proof_service
+    }
+
+    /// KVService operations.
+    pub fn kv_service(&self) -> &kv_service::Client {
+        &self.kv_service
+    }
+
+    /// Start a new [ProofProcedure] using this client's endpoint and connection pool.
+    pub fn proof_procedure(
+        &self,
+        action: Action,
+        avatar: Secp256k1KeyPair,
+        platform: Platform,
+        identity: &str,
+    ) -> ProofProcedure {
+        let mut procedure = ProofProcedure::new(
+            self.proof_service.endpoint.clone(),
+            action,
+            avatar,
+            platform,
+            identity,
+        )
+        .with_transport(self.proof_service.transport.clone());
+        procedure.timeout = self.proof_service.timeout;
+        procedure.metrics = self.proof_service.metrics.clone();
+        procedure
+    }
+
+    /// Start a new [KVProcedure] using this client's endpoint and connection pool.
+    pub fn kv_procedure(
+        &self,
+        action: Action,
+        avatar: Secp256k1KeyPair,
+        platform: Platform,
+        identity: &str,
+        patch: Value,
+    ) -> KVProcedure {
+        let mut procedure = KVProcedure::new(
+            self.kv_service.endpoint.clone(),
+            action,
+            avatar,
+            platform,
+            identity,
+            patch,
+        )
+        .with_transport(self.kv_service.transport.clone());
+        procedure.timeout = self.kv_service.timeout;
+        procedure.metrics = self.kv_service.metrics.clone();
+        procedure
+    }
+}
+
+impl Default for NextIdClient {
+    fn default() -> Self {
+        Self::new(ClientConfig::default())
+    }
+}
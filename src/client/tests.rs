@@ -0,0 +1,89 @@
+use super::{ClientConfig, NextIdClient};
+use crate::{
+    kv_service, proof_service,
+    types::{Error, Result},
+    util::{
+        http::{Transport, TransportRequest, TransportResponse},
+        retry::RetryPolicy,
+    },
+};
+use http::StatusCode;
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// Answers every request with a `hello` after `delay`, recording the requested URLs without query.
+struct CountingTransport {
+    delay: Duration,
+    urls: Mutex<Vec<String>>,
+}
+
+impl CountingTransport {
+    fn new(delay: Duration) -> Arc<Self> {
+        Arc::new(Self {
+            delay,
+            urls: Mutex::new(vec![]),
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl Transport for CountingTransport {
+    async fn send(&self, request: TransportRequest) -> Result<TransportResponse> {
+        let url = &request.url[..url::Position::AfterPath];
+        self.urls.lock().unwrap().push(url.to_string());
+        tokio::time::sleep(self.delay).await;
+        Ok(TransportResponse {
+            status: StatusCode::OK,
+            headers: Default::default(),
+            body: br#"{"hello": "world"}"#.to_vec(),
+        })
+    }
+}
+
+fn config(timeout: Duration) -> ClientConfig {
+    ClientConfig {
+        proof_service: proof_service::Endpoint::Custom("https://proof.example.com".into()),
+        kv_service: kv_service::Endpoint::Custom("https://kv.example.com".into()),
+        timeout: Some(timeout),
+        retry: RetryPolicy::disabled(),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_client_shares_transport() -> Result<()> {
+    let http = CountingTransport::new(Duration::ZERO);
+    let client = NextIdClient::layered(config(Duration::from_secs(1)), http.clone());
+    assert!(Arc::ptr_eq(
+        &client.proof_service().transport,
+        &client.kv_service().transport
+    ));
+
+    client.proof_service().healthz().await?;
+    client.kv_service().healthz().await?;
+    client.clone().proof_service().healthz().await?;
+    assert_eq!(
+        vec![
+            "https://proof.example.com/healthz",
+            "https://kv.example.com/healthz",
+            "https://proof.example.com/healthz",
+        ],
+        *http.urls.lock().unwrap()
+    );
+    Ok(())
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_client_applies_timeout() {
+    let http = CountingTransport::new(Duration::from_secs(10));
+    let client = NextIdClient::layered(config(Duration::from_secs(1)), http.clone());
+    assert_eq!(Some(Duration::from_secs(1)), client.proof_service().timeout);
+
+    let result = client.proof_service().healthz().await;
+    assert!(matches!(result, Err(Error::Timeout(timeout)) if timeout == Duration::from_secs(1)));
+    let result = client.kv_service().healthz().await;
+    assert!(matches!(result, Err(Error::Timeout(timeout)) if timeout == Duration::from_secs(1)));
+    assert_eq!(2, http.urls.lock().unwrap().len());
+}
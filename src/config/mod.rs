@@ -0,0 +1,250 @@
+#[cfg(test)]
+mod tests;
+
+use crate::{
+    client::ClientConfig,
+    types::{Error, Result},
+};
+use serde::Deserialize;
+use std::{path::Path, str::FromStr, time::Duration};
+use url::Url;
+
+/// Environment variable naming a config file read by [Settings::from_env].
+pub const CONFIG_PATH_VAR: &str = "NEXTID_CONFIG";
+
+/// Partial [ClientConfig], as written in a config file or in environment variables.
+/// Unset fields keep the value of the config it is applied to.
+///
+/// In a file (TOML needs the `toml` feature, JSON is always supported):
+/// ```toml
+/// proof_service = "staging"               # `production`, `staging` or a root URL
+/// kv_service = "https://kv.example.com"
+/// proof_service_fallbacks = ["production"]
+/// timeout_ms = 10000                      # `0` waits forever
+/// connect_timeout_ms = 3000
+///
+/// [retry]
+/// max_attempts = 5
+/// initial_backoff_ms = 100
+/// max_backoff_ms = 5000
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    /// ProofService server: `production`, `staging` or the root URL of a custom server.
+    pub proof_service: Option<String>,
+    /// KVService server, in the same form as `proof_service`.
+    pub kv_service: Option<String>,
+    /// See [ClientConfig::proof_service_fallbacks].
+    pub proof_service_fallbacks: Option<Vec<String>>,
+    /// See [ClientConfig::kv_service_fallbacks].
+    pub kv_service_fallbacks: Option<Vec<String>>,
+    /// Deadline of each API call in milliseconds. `0` waits forever.
+    pub timeout_ms: Option<u64>,
+    /// TCP connect timeout in milliseconds. `0` waits forever.
+    pub connect_timeout_ms: Option<u64>,
+    pub retry: RetrySettings,
+}
+
+/// Partial [RetryPolicy](crate::util::retry::RetryPolicy).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetrySettings {
+    /// Max attempts in total, including the first one. `1` disables retrying.
+    pub max_attempts: Option<u32>,
+    /// Backoff before the second attempt, in milliseconds.
+    pub initial_backoff_ms: Option<u64>,
+    /// Upper bound of a single backoff, in milliseconds.
+    pub max_backoff_ms: Option<u64>,
+}
+
+impl Settings {
+    /// Read a `.toml` or `.json` config file.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)?;
+        let invalid = |err: &dyn std::fmt::Display| {
+            Error::ConfigError(format!("{}: {}", path.display(), err))
+        };
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => serde_json::from_str(&content).map_err(|err| invalid(&err)),
+            #[cfg(feature = "toml")]
+            Some("toml") => toml::from_str(&content).map_err(|err| invalid(&err)),
+            #[cfg(not(feature = "toml"))]
+            Some("toml") => Err(invalid(&"TOML config files need the `toml` feature")),
+            _ => Err(invalid(
+                &"unknown config file format, expected .toml or .json",
+            )),
+        }
+    }
+
+    /// Read environment variables, on top of the config file named by `NEXTID_CONFIG` if any.
+    /// Empty variables are ignored.
+    ///
+    /// | Variable                              | Field                       |
+    /// |---------------------------------------|-----------------------------|
+    /// | `NEXTID_PROOF_SERVICE_URL`            | `proof_service`             |
+    /// | `NEXTID_KV_SERVICE_URL`               | `kv_service`                |
+    /// | `NEXTID_PROOF_SERVICE_FALLBACK_URLS`  | `proof_service_fallbacks` (comma-separated) |
+    /// | `NEXTID_KV_SERVICE_FALLBACK_URLS`     | `kv_service_fallbacks` (comma-separated)    |
+    /// | `NEXTID_TIMEOUT_MS`                   | `timeout_ms`                |
+    /// | `NEXTID_CONNECT_TIMEOUT_MS`           | `connect_timeout_ms`        |
+    /// | `NEXTID_RETRY_MAX_ATTEMPTS`           | `retry.max_attempts`        |
+    /// | `NEXTID_RETRY_INITIAL_BACKOFF_MS`     | `retry.initial_backoff_ms`  |
+    /// | `NEXTID_RETRY_MAX_BACKOFF_MS`         | `retry.max_backoff_ms`      |
+    pub fn from_env() -> Result<Self> {
+        Self::from_vars(std::env::vars())
+    }
+
+    /// [Settings::from_env] reading `vars` instead of the process environment.
+    pub(crate) fn from_vars(vars: impl IntoIterator<Item = (String, String)>) -> Result<Self> {
+        let vars: Vec<(String, String)> = vars
+            .into_iter()
+            .filter(|(key, value)| key.starts_with("NEXTID_") && !value.trim().is_empty())
+            .collect();
+        let mut settings = match vars.iter().find(|(key, _)| key == CONFIG_PATH_VAR) {
+            Some((_, path)) => Self::from_file(path.trim())?,
+            None => Self::default(),
+        };
+        for (key, value) in vars {
+            let value = value.trim();
+            match key.as_str() {
+                "NEXTID_PROOF_SERVICE_URL" => settings.proof_service = Some(value.to_string()),
+                "NEXTID_KV_SERVICE_URL" => settings.kv_service = Some(value.to_string()),
+                "NEXTID_PROOF_SERVICE_FALLBACK_URLS" => {
+                    settings.proof_service_fallbacks = Some(split_list(value))
+                }
+                "NEXTID_KV_SERVICE_FALLBACK_URLS" => {
+                    settings.kv_service_fallbacks = Some(split_list(value))
+                }
+                "NEXTID_TIMEOUT_MS" => settings.timeout_ms = Some(parse_var(&key, value)?),
+                "NEXTID_CONNECT_TIMEOUT_MS" => {
+                    settings.connect_timeout_ms = Some(parse_var(&key, value)?)
+                }
+                "NEXTID_RETRY_MAX_ATTEMPTS" => {
+                    settings.retry.max_attempts = Some(parse_var(&key, value)?)
+                }
+                "NEXTID_RETRY_INITIAL_BACKOFF_MS" => {
+                    settings.retry.initial_backoff_ms = Some(parse_var(&key, value)?)
+                }
+                "NEXTID_RETRY_MAX_BACKOFF_MS" => {
+                    settings.retry.max_backoff_ms = Some(parse_var(&key, value)?)
+                }
+                _ => {}
+            }
+        }
+        Ok(settings)
+    }
+
+    /// Validate these settings and write them into `target`, which is left untouched on error.
+    pub fn apply(&self, target: &mut ClientConfig) -> Result<()> {
+        let mut config = target.clone();
+        if let Some(endpoint) = &self.proof_service {
+            config.proof_service = endpoint.parse()?;
+        }
+        if let Some(endpoint) = &self.kv_service {
+            config.kv_service = endpoint.parse()?;
+        }
+        if let Some(endpoints) = &self.proof_service_fallbacks {
+            config.proof_service_fallbacks = parse_all(endpoints)?;
+        }
+        if let Some(endpoints) = &self.kv_service_fallbacks {
+            config.kv_service_fallbacks = parse_all(endpoints)?;
+        }
+        if let Some(timeout) = self.timeout_ms {
+            config.timeout = millis(timeout);
+        }
+        if let Some(timeout) = self.connect_timeout_ms {
+            config.http.connect_timeout = millis(timeout);
+        }
+
+        let retry = &mut config.retry;
+        if let Some(max_attempts) = self.retry.max_attempts {
+            if max_attempts == 0 {
+                return Err(Error::ConfigError(
+                    "retry.max_attempts must be at least 1".into(),
+                ));
+            }
+            retry.max_attempts = max_attempts;
+        }
+        if let Some(backoff) = self.retry.initial_backoff_ms {
+            retry.initial_backoff = Duration::from_millis(backoff);
+        }
+        if let Some(backoff) = self.retry.max_backoff_ms {
+            retry.max_backoff = Duration::from_millis(backoff);
+        }
+        if retry.initial_backoff > retry.max_backoff {
+            return Err(Error::ConfigError(format!(
+                "retry initial backoff ({:?}) exceeds max backoff ({:?})",
+                retry.initial_backoff, retry.max_backoff
+            )));
+        }
+        *target = config;
+        Ok(())
+    }
+}
+
+impl ClientConfig {
+    /// Default config, overridden by a `.toml` or `.json` file (see [Settings]).
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let mut config = Self::default();
+        Settings::from_file(path)?.apply(&mut config)?;
+        Ok(config)
+    }
+
+    /// Default config, overridden by the file named by `NEXTID_CONFIG` then by
+    /// other `NEXTID_*` environment variables (see [Settings::from_env]).
+    /// # Examples
+    /// ```rust
+    /// # use nextid_sdk::client::{ClientConfig, NextIdClient};
+    /// // e.g. NEXTID_PROOF_SERVICE_URL=staging NEXTID_TIMEOUT_MS=5000
+    /// let client = NextIdClient::new(ClientConfig::from_env().unwrap());
+    /// ```
+    pub fn from_env() -> Result<Self> {
+        let mut config = Self::default();
+        Settings::from_env()?.apply(&mut config)?;
+        Ok(config)
+    }
+}
+
+/// Validate a server root URL and put it in canonical form, without trailing slash.
+pub(crate) fn normalize_base_url(url: &str) -> Result<String> {
+    let invalid = |reason: &dyn std::fmt::Display| {
+        Error::ConfigError(format!("invalid server URL {:?}: {}", url, reason))
+    };
+    let parsed = Url::parse(url.trim()).map_err(|err| invalid(&err))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(invalid(&"scheme must be http or https"));
+    }
+    if parsed.query().is_some() || parsed.fragment().is_some() {
+        return Err(invalid(&"query and fragment are not allowed"));
+    }
+    Ok(parsed.as_str().trim_end_matches('/').to_string())
+}
+
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn parse_var<T: FromStr>(key: &str, value: &str) -> Result<T>
+where
+    T::Err: std::fmt::Display,
+{
+    value
+        .parse()
+        .map_err(|err| Error::ConfigError(format!("{}={:?}: {}", key, value, err)))
+}
+
+fn parse_all<E: FromStr<Err = Error>>(endpoints: &[String]) -> Result<Vec<E>> {
+    endpoints.iter().map(|endpoint| endpoint.parse()).collect()
+}
+
+/// `0` means no limit.
+fn millis(value: u64) -> Option<Duration> {
+    (value > 0).then(|| Duration::from_millis(value))
+}
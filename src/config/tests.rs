@@ -0,0 +1,193 @@
+use super::{Settings, CONFIG_PATH_VAR};
+use crate::{
+    client::ClientConfig,
+    kv_service,
+    proof_service::{self, Platform},
+    types::{Error, Result},
+    util::cassette::ReplayTransport,
+};
+use std::{path::PathBuf, sync::Arc, time::Duration};
+
+fn vars(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+    pairs
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+/// Write `content` to a fresh file named `name` in the temp directory.
+fn temp_file(name: &str, content: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("nextid_sdk_config_{}", rand::random::<u64>()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    std::fs::write(&path, content).unwrap();
+    path
+}
+
+#[test]
+fn test_endpoint_from_str() -> Result<()> {
+    assert!(matches!(
+        "Production".parse()?,
+        proof_service::Endpoint::Production
+    ));
+    assert!(matches!(
+        " staging ".parse()?,
+        kv_service::Endpoint::Staging
+    ));
+
+    let endpoint: proof_service::Endpoint = "HTTPS://Proof.Example.com:443/api//".parse()?;
+    assert_eq!("https://proof.example.com/api", endpoint.base_url());
+    let endpoint: kv_service::Endpoint = "http://127.0.0.1:8080".parse()?;
+    assert_eq!("http://127.0.0.1:8080", endpoint.base_url());
+
+    for invalid in [
+        "proof.example.com",
+        "ftp://proof.example.com",
+        "https://proof.example.com?a=1",
+        "https://proof.example.com/#top",
+    ] {
+        let err = invalid.parse::<proof_service::Endpoint>().unwrap_err();
+        assert!(matches!(err, Error::ConfigError(_)), "{}", invalid);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_custom_endpoint_trailing_slash() -> Result<()> {
+    // Replay matches paths strictly: `//v1/proof` would not be found.
+    let replay = ReplayTransport::from_file("fixtures/proof_service.json")?;
+    let client = proof_service::Endpoint::Custom("https://proof.example.com/".into())
+        .with_transport(Arc::new(replay));
+    let avatars = client.find_by(Platform::Twitter, "yeiwb", false).await?;
+    assert!(!avatars.is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_from_vars() -> Result<()> {
+    let settings = Settings::from_vars(vars(&[
+        ("NEXTID_PROOF_SERVICE_URL", "staging"),
+        ("NEXTID_KV_SERVICE_URL", "https://kv.example.com/"),
+        (
+            "NEXTID_PROOF_SERVICE_FALLBACK_URLS",
+            "https://a.example.com, production,",
+        ),
+        ("NEXTID_TIMEOUT_MS", "0"),
+        ("NEXTID_CONNECT_TIMEOUT_MS", "1500"),
+        ("NEXTID_RETRY_MAX_ATTEMPTS", "5"),
+        ("NEXTID_RETRY_INITIAL_BACKOFF_MS", ""),
+        ("PATH", "/usr/bin"),
+    ]))?;
+    let mut config = ClientConfig::default();
+    settings.apply(&mut config)?;
+
+    assert!(matches!(
+        config.proof_service,
+        proof_service::Endpoint::Staging
+    ));
+    assert_eq!("https://kv.example.com", config.kv_service.base_url());
+    let fallbacks: Vec<String> = config
+        .proof_service_fallbacks
+        .iter()
+        .map(proof_service::Endpoint::base_url)
+        .collect();
+    assert_eq!(
+        vec!["https://a.example.com", "https://proof-service.next.id"],
+        fallbacks
+    );
+    assert_eq!(None, config.timeout);
+    assert_eq!(
+        Some(Duration::from_millis(1500)),
+        config.http.connect_timeout
+    );
+    assert_eq!(5, config.retry.max_attempts);
+    assert_eq!(
+        ClientConfig::default().retry.initial_backoff,
+        config.retry.initial_backoff
+    );
+
+    let err = Settings::from_vars(vars(&[("NEXTID_TIMEOUT_MS", "10s")])).unwrap_err();
+    assert!(err.to_string().contains("NEXTID_TIMEOUT_MS"));
+    Ok(())
+}
+
+#[test]
+fn test_env_overrides_file() -> Result<()> {
+    let path = temp_file(
+        "nextid.json",
+        r#"{
+            "proof_service": "https://proof.example.com",
+            "kv_service": "staging",
+            "timeout_ms": 10000,
+            "retry": { "max_attempts": 2, "max_backoff_ms": 1000 }
+        }"#,
+    );
+    let settings = Settings::from_vars(vars(&[
+        (CONFIG_PATH_VAR, path.to_str().unwrap()),
+        ("NEXTID_KV_SERVICE_URL", "production"),
+        ("NEXTID_RETRY_MAX_ATTEMPTS", "4"),
+    ]))?;
+    let mut config = ClientConfig::default();
+    settings.apply(&mut config)?;
+
+    assert_eq!("https://proof.example.com", config.proof_service.base_url());
+    assert!(matches!(
+        config.kv_service,
+        kv_service::Endpoint::Production
+    ));
+    assert_eq!(Some(Duration::from_secs(10)), config.timeout);
+    assert_eq!(4, config.retry.max_attempts);
+    assert_eq!(Duration::from_secs(1), config.retry.max_backoff);
+    Ok(())
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn test_toml_file() -> Result<()> {
+    let path = temp_file(
+        "nextid.toml",
+        r#"
+            proof_service = "staging"
+            kv_service_fallbacks = ["production"]
+
+            [retry]
+            initial_backoff_ms = 50
+        "#,
+    );
+    let config = ClientConfig::from_file(path)?;
+    assert!(matches!(
+        config.proof_service,
+        proof_service::Endpoint::Staging
+    ));
+    assert_eq!(1, config.kv_service_fallbacks.len());
+    assert_eq!(Duration::from_millis(50), config.retry.initial_backoff);
+    Ok(())
+}
+
+#[test]
+fn test_invalid_settings() {
+    let typo = temp_file("nextid.json", r#"{ "proof_servce": "staging" }"#);
+    assert!(matches!(
+        ClientConfig::from_file(typo),
+        Err(Error::ConfigError(_))
+    ));
+    let yaml = temp_file("nextid.yaml", "proof_service: staging");
+    assert!(matches!(
+        ClientConfig::from_file(yaml),
+        Err(Error::ConfigError(_))
+    ));
+
+    let mut config = ClientConfig::default();
+    for settings in [
+        r#"{ "retry": { "max_attempts": 0 } }"#,
+        r#"{ "retry": { "initial_backoff_ms": 2000, "max_backoff_ms": 1000 } }"#,
+        r#"{ "kv_service_fallbacks": ["kv.example.com"] }"#,
+    ] {
+        let settings: Settings = serde_json::from_str(settings).unwrap();
+        assert!(
+            matches!(settings.apply(&mut config), Err(Error::ConfigError(_))),
+            "{:?}",
+            settings
+        );
+    }
+}
@@ -1,18 +1,22 @@
 mod procedure;
 mod types;
 
+pub use self::types::{Health, KVAvatar, KVSingleProof};
 pub use procedure::KVProcedure;
 
 use self::types::raw::QueryResponse;
-use self::types::{KVAvatar, KVSingleProof};
+use crate::config::normalize_base_url;
 use crate::proof_service::Platform;
-use crate::types::Result;
+use crate::types::{Error, Result};
 use crate::util::crypto::Secp256k1KeyPair;
 use crate::util::hex_encode;
-use crate::util::http::request;
+use crate::util::http::{default_transport, request, Call, Transport, DEFAULT_TIMEOUT};
+use crate::util::metrics::{MetricsRecorder, Operation, Service};
 use http::Method;
-use hyper::Body;
 use std::borrow::Borrow;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
 use url::Url;
 
 /// KVService endpoint
@@ -29,6 +33,47 @@ pub enum Endpoint {
 }
 
 impl Endpoint {
+    /// Bind this endpoint to a custom [Transport].
+    /// # Examples
+    /// ```rust
+    /// # use std::sync::Arc;
+    /// # use nextid_sdk::{kv_service::Endpoint, util::http::HyperTransport};
+    /// let client = Endpoint::Staging.with_transport(Arc::new(HyperTransport::new()));
+    /// ```
+    pub fn with_transport(&self, transport: Arc<dyn Transport>) -> Client {
+        Client {
+            endpoint: self.clone(),
+            transport,
+            timeout: Some(DEFAULT_TIMEOUT),
+            metrics: None,
+        }
+    }
+
+    /// Root URL of the server, without trailing slash.
+    /// # Examples
+    /// ```rust
+    /// # use nextid_sdk::kv_service::Endpoint;
+    /// assert_eq!("https://kv-service.next.id", Endpoint::Production.base_url());
+    /// let custom = Endpoint::Custom("https://kv-service.example.com/".into());
+    /// assert_eq!("https://kv-service.example.com", custom.base_url());
+    /// ```
+    pub fn base_url(&self) -> String {
+        match self {
+            Endpoint::Production => "https://kv-service.next.id".to_string(),
+            Endpoint::Staging => "https://kv-service.nextnext.id".to_string(),
+            Endpoint::Custom(url) => url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// Label of this endpoint variant in metrics.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Endpoint::Production => "production",
+            Endpoint::Staging => "staging",
+            Endpoint::Custom(_) => "custom",
+        }
+    }
+
     /// Concat server API URL.
     fn uri<I, K, V>(&self, path: &str, query: I) -> Result<Url>
     where
@@ -37,12 +82,7 @@ impl Endpoint {
         K: AsRef<str>,
         V: AsRef<str>,
     {
-        use Endpoint::*;
-        let base = match self {
-            Production => format!("https://kv-service.next.id/{}", path),
-            Staging => format!("https://kv-service.nextnext.id/{}", path),
-            Custom(url) => format!("{}/{}", url, path),
-        };
+        let base = format!("{}/{}", self.base_url(), path);
         Url::parse_with_params(&base, query).map_err(|e| e.into())
     }
 
@@ -51,19 +91,20 @@ impl Endpoint {
     /// ```rust
     /// # #[tokio::main]
     /// # async fn main() {
+    /// # use std::sync::Arc;
     /// # use nextid_sdk::kv_service::Endpoint;
-    /// # use nextid_sdk::util::crypto::Secp256k1KeyPair;
+    /// # use nextid_sdk::util::{cassette::ReplayTransport, crypto::Secp256k1KeyPair};
+    /// # let replay = ReplayTransport::from_file("fixtures/kv_service.json").unwrap();
+    /// # let endpoint = Endpoint::Staging.with_transport(Arc::new(replay));
     /// let avatar = Secp256k1KeyPair::from_pk_hex("0x047e55e1b78e873c6f7d585064b41cd2735000bacc0092fe947c11ab7742ed351fef59c4f5d558d14a031bb09e44877f9e61f89993f895eb8fa6cfaafe74f6f55c").unwrap();
-    /// let result = Endpoint::Staging.find_by_avatar(&avatar).await.unwrap();
+    /// let result = endpoint.find_by_avatar(&avatar).await.unwrap();
     /// assert!(result.len() > 0);
     /// # }
     /// ```
     pub async fn find_by_avatar(&self, avatar: &Secp256k1KeyPair) -> Result<Vec<KVSingleProof>> {
-        let pubkey_compress_hex = format!("0x{}", hex_encode(&avatar.pk.serialize_compressed()));
-        let uri = self.uri("v1/kv", &[("avatar", pubkey_compress_hex)])?;
-        let response: QueryResponse = request(Method::GET, &uri, Body::empty()).await?;
-
-        Ok(response.proofs)
+        self.with_transport(default_transport())
+            .find_by_avatar(avatar)
+            .await
     }
 
     /// Get all KV records under a given platform / identity pair.
@@ -71,9 +112,13 @@ impl Endpoint {
     /// ```rust
     /// # #[tokio::main]
     /// # async fn main() {
+    /// # use std::sync::Arc;
     /// # use nextid_sdk::kv_service::Endpoint;
     /// # use nextid_sdk::proof_service::Platform;
-    /// let result = Endpoint::Staging.find_by_platform_identity(Platform::Twitter, "yeiwb").await.unwrap();
+    /// # use nextid_sdk::util::cassette::ReplayTransport;
+    /// # let replay = ReplayTransport::from_file("fixtures/kv_service.json").unwrap();
+    /// # let endpoint = Endpoint::Staging.with_transport(Arc::new(replay));
+    /// let result = endpoint.find_by_platform_identity(Platform::Twitter, "yeiwb").await.unwrap();
     /// assert!(result.len() > 0);
     /// # }
     /// ```
@@ -82,15 +127,157 @@ impl Endpoint {
         platform: Platform,
         identity: &str,
     ) -> Result<Vec<KVAvatar>> {
-        let uri = self.uri(
+        self.with_transport(default_transport())
+            .find_by_platform_identity(platform, identity)
+            .await
+    }
+
+    /// Check the server is up, and get what it reports about itself.
+    /// # Examples
+    /// ```rust
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// # use std::sync::Arc;
+    /// # use nextid_sdk::kv_service::Endpoint;
+    /// # use nextid_sdk::util::cassette::ReplayTransport;
+    /// # let replay = ReplayTransport::from_file("fixtures/kv_service.json").unwrap();
+    /// # let endpoint = Endpoint::Staging.with_transport(Arc::new(replay));
+    /// let health = endpoint.healthz().await.unwrap();
+    /// assert_eq!("kv server", health.hello);
+    /// # }
+    /// ```
+    pub async fn healthz(&self) -> Result<Health> {
+        self.with_transport(default_transport()).healthz().await
+    }
+}
+
+/// Parse `production`, `staging` or the root URL of a custom server.
+/// URLs must be `http` / `https` without query nor fragment, and are normalized
+/// (e.g. lowercase host, no trailing slash).
+/// # Examples
+/// ```rust
+/// # use nextid_sdk::kv_service::Endpoint;
+/// let endpoint: Endpoint = "https://kv-service.EXAMPLE.com/".parse().unwrap();
+/// assert_eq!("https://kv-service.example.com", endpoint.base_url());
+/// assert!("ftp://kv-service.example.com".parse::<Endpoint>().is_err());
+/// ```
+impl FromStr for Endpoint {
+    type Err = Error;
+
+    fn from_str(endpoint: &str) -> Result<Self> {
+        match endpoint.trim().to_ascii_lowercase().as_str() {
+            "production" => Ok(Endpoint::Production),
+            "staging" => Ok(Endpoint::Staging),
+            _ => Ok(Endpoint::Custom(normalize_base_url(endpoint)?)),
+        }
+    }
+}
+
+/// KVService [Endpoint] bound to a [Transport].
+#[derive(Clone)]
+pub struct Client {
+    pub endpoint: Endpoint,
+    pub transport: Arc<dyn Transport>,
+    /// Deadline of each API call, retries included. `None` waits forever.
+    pub timeout: Option<Duration>,
+    /// Receives metrics of each API call.
+    pub metrics: Option<Arc<dyn MetricsRecorder>>,
+}
+
+impl From<Endpoint> for Client {
+    fn from(endpoint: Endpoint) -> Self {
+        endpoint.with_transport(default_transport())
+    }
+}
+
+impl Client {
+    /// Same client, but with another deadline for each API call.
+    /// The TCP connect timeout is not affected: it belongs to the transport
+    /// (see [HttpConfig::connect_timeout](crate::util::http::HttpConfig::connect_timeout)).
+    /// # Examples
+    /// ```rust
+    /// # use std::time::Duration;
+    /// # use nextid_sdk::kv_service::Endpoint;
+    /// let client = Endpoint::Staging.with_transport(nextid_sdk::util::http::default_transport());
+    /// let impatient = client.with_timeout(Duration::from_secs(1));
+    /// ```
+    pub fn with_timeout(&self, timeout: Duration) -> Self {
+        Self {
+            timeout: Some(timeout),
+            ..self.clone()
+        }
+    }
+
+    /// Same client, reporting metrics of each API call to `metrics`.
+    pub fn with_metrics(&self, metrics: Arc<dyn MetricsRecorder>) -> Self {
+        Self {
+            metrics: Some(metrics),
+            ..self.clone()
+        }
+    }
+
+    /// Settings of a single API call of `operation`.
+    fn call(&self, operation: &'static str) -> Call<'_> {
+        Call {
+            transport: self.transport.as_ref(),
+            operation: Operation {
+                service: Service::KVService,
+                endpoint: self.endpoint.label(),
+                name: operation,
+            },
+            timeout: self.timeout,
+            metrics: self.metrics.as_deref(),
+        }
+    }
+
+    /// Same as [Endpoint::find_by_avatar], but requests through `self.transport`.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "KVService.find_by_avatar",
+            skip_all,
+            fields(avatar = %hex_encode(&avatar.pk.serialize_compressed()))
+        )
+    )]
+    pub async fn find_by_avatar(&self, avatar: &Secp256k1KeyPair) -> Result<Vec<KVSingleProof>> {
+        let pubkey_compress_hex = format!("0x{}", hex_encode(&avatar.pk.serialize_compressed()));
+        let uri = self
+            .endpoint
+            .uri("v1/kv", &[("avatar", pubkey_compress_hex)])?;
+        let response: QueryResponse =
+            request(self.call("find_by_avatar"), Method::GET, &uri, vec![]).await?;
+
+        Ok(response.proofs)
+    }
+
+    /// Same as [Endpoint::find_by_platform_identity], but requests through `self.transport`.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "KVService.find_by_platform_identity",
+            skip_all,
+            fields(%platform, identity)
+        )
+    )]
+    pub async fn find_by_platform_identity(
+        &self,
+        platform: Platform,
+        identity: &str,
+    ) -> Result<Vec<KVAvatar>> {
+        let uri = self.endpoint.uri(
             "v1/kv/by_identity",
             &[
                 ("platform", platform.to_string().as_str()),
                 ("identity", identity),
             ],
         )?;
-        let response: types::raw::QueryIdentityResponse =
-            request(Method::GET, &uri, Body::empty()).await?;
+        let response: types::raw::QueryIdentityResponse = request(
+            self.call("find_by_platform_identity"),
+            Method::GET,
+            &uri,
+            vec![],
+        )
+        .await?;
         response
             .values
             .into_iter()
@@ -103,4 +290,16 @@ impl Endpoint {
             })
             .collect()
     }
+
+    /// Same as [Endpoint::healthz], but requests through `self.transport`.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "KVService.healthz", skip_all)
+    )]
+    pub async fn healthz(&self) -> Result<Health> {
+        let uri = self
+            .endpoint
+            .uri::<Vec<(String, String)>, _, _>("healthz", vec![])?;
+        request(self.call("healthz"), Method::GET, &uri, vec![]).await
+    }
 }
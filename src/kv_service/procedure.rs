@@ -1,6 +1,7 @@
 use chrono::NaiveDateTime;
 use http::Method;
 use serde_json::Value;
+use std::{sync::Arc, time::Duration};
 
 use super::{
     types::{
@@ -12,11 +13,23 @@ use super::{
 use crate::{
     proof_service::{Action, Platform},
     types::{Error, Result},
-    util::{base64_encode, crypto::Secp256k1KeyPair, hex_encode, http::request, ts_to_naive},
+    util::{
+        base64_encode,
+        crypto::Secp256k1KeyPair,
+        hex_encode,
+        http::{default_transport, request, Call, Transport, DEFAULT_TIMEOUT},
+        metrics::{MetricsRecorder, Operation, Service},
+        try_ts_to_naive,
+    },
 };
 
 pub struct KVProcedure {
     pub endpoint: Endpoint,
+    pub transport: Arc<dyn Transport>,
+    /// Deadline of each request, retries included. `None` waits forever.
+    pub timeout: Option<Duration>,
+    /// Receives metrics of each request.
+    pub metrics: Option<Arc<dyn MetricsRecorder>>,
     pub action: Action,
     pub avatar: Secp256k1KeyPair,
     pub platform: Platform,
@@ -41,6 +54,9 @@ impl KVProcedure {
     ) -> Self {
         KVProcedure {
             endpoint,
+            transport: default_transport(),
+            timeout: Some(DEFAULT_TIMEOUT),
+            metrics: None,
             action,
             avatar,
             platform,
@@ -53,6 +69,38 @@ impl KVProcedure {
         }
     }
 
+    /// Send all requests of this procedure through `transport`.
+    pub fn with_transport(mut self, transport: Arc<dyn Transport>) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Give up each request of this procedure after `timeout`.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Report metrics of each request of this procedure to `metrics`.
+    pub fn with_metrics(mut self, metrics: Arc<dyn MetricsRecorder>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Settings of a single request of `operation`.
+    fn call(&self, operation: &'static str) -> Call<'_> {
+        Call {
+            transport: self.transport.as_ref(),
+            operation: Operation {
+                service: Service::KVService,
+                endpoint: self.endpoint.label(),
+                name: operation,
+            },
+            timeout: self.timeout,
+            metrics: self.metrics.as_deref(),
+        }
+    }
+
     /// Request for signature payloads from KVService.
     /// # Examples
     /// ```rust
@@ -64,11 +112,23 @@ impl KVProcedure {
     /// # use nextid_sdk::util::crypto::Secp256k1KeyPair;
     /// # use serde_json::json;
     /// # let avatar = Secp256k1KeyPair::from_pk_hex("0x020d2ee3a597c24c66717dba01d7d14cb55e307834fe23428bd85c64249111f08a").unwrap();
-    /// let mut procedure = KVProcedure::new(Endpoint::Staging, Action::Create, avatar, Platform::Twitter, "yeiwb", json!({"test": "abc123"}));
+    /// # use nextid_sdk::util::cassette::ReplayTransport;
+    /// # let replay = ReplayTransport::from_file("fixtures/kv_service.json").unwrap();
+    /// let mut procedure = KVProcedure::new(Endpoint::Staging, Action::Create, avatar, Platform::Twitter, "yeiwb", json!({"test": "abc123"}))
+    /// #   .with_transport(std::sync::Arc::new(replay))
+    /// ;
     /// assert_eq!((), procedure.get_payload().await.unwrap());
     /// # assert!(procedure.sign_payload.unwrap().len() > 0)
     /// # }
     /// ```
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "KVProcedure.get_payload",
+            skip_all,
+            fields(action = %self.action, platform = %self.platform, identity = %self.identity)
+        )
+    )]
     pub async fn get_payload(&mut self) -> Result<()> {
         let url = self
             .endpoint
@@ -81,14 +141,15 @@ impl KVProcedure {
             patch: &self.patch,
         };
         let response: PayloadResponse = request(
+            self.call("payload"),
             Method::POST,
             &url,
-            serde_json::to_vec(&request_body)?.into(),
+            serde_json::to_vec(&request_body)?,
         )
         .await?;
 
         self.uuid = Some(response.uuid);
-        self.created_at = Some(ts_to_naive(response.created_at, 0));
+        self.created_at = Some(try_ts_to_naive(response.created_at, 0)?);
         self.sign_payload = Some(response.sign_payload);
 
         Ok(())
@@ -96,6 +157,14 @@ impl KVProcedure {
 
     /// Submit the KV patch to KVService.
     /// If success, returns all KVs under this avatar.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "KVProcedure.submit",
+            skip_all,
+            fields(action = %self.action, platform = %self.platform, identity = %self.identity)
+        )
+    )]
     pub async fn submit(&mut self, avatar_signature: Vec<u8>) -> Result<Vec<KVSingleProof>> {
         // Valiadte signature locally before requesting.
         let recovered = Secp256k1KeyPair::recover_from_personal_signature(
@@ -103,7 +172,7 @@ impl KVProcedure {
             self.sign_payload.as_ref().unwrap(),
         )?;
         if recovered.pk != self.avatar.pk {
-            return Err(Error::ServerError(
+            return Err(Error::ValidationError(
                 "KVProcedure.submit(): Pubkey recovered from signature mismatches `self.avatar`."
                     .into(),
             ));
@@ -121,13 +190,14 @@ impl KVProcedure {
             identity: &self.identity,
             signature: &signature,
             uuid: self.uuid.as_ref().unwrap(),
-            created_at: self.created_at.as_ref().unwrap().timestamp(),
+            created_at: self.created_at.as_ref().unwrap().and_utc().timestamp(),
             patch: &self.patch,
         };
         let response: QueryResponse = request(
+            self.call("submit"),
             Method::POST,
             &url,
-            serde_json::to_vec(&request_body)?.into(),
+            serde_json::to_vec(&request_body)?,
         )
         .await?;
 
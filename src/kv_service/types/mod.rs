@@ -2,7 +2,7 @@ pub(crate) mod raw;
 
 use crate::{proof_service::Platform, util::crypto::Secp256k1KeyPair};
 use serde::Deserialize;
-use serde_json::Value;
+use serde_json::{Map, Value};
 
 pub struct KVAvatar {
     pub avatar: Secp256k1KeyPair,
@@ -15,3 +15,19 @@ pub struct KVSingleProof {
     pub identity: String,
     pub content: serde_json::Value,
 }
+
+/// Status reported by `GET /healthz` of a KVService server.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Health {
+    /// Greeting of the server, e.g. `kv server`.
+    pub hello: String,
+    /// Deployment environment, e.g. `production`, if exposed.
+    #[serde(default)]
+    pub environment: Option<String>,
+    /// Server build version, if exposed.
+    #[serde(default)]
+    pub version: Option<String>,
+    /// Any other field reported by the server.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
@@ -6,6 +6,7 @@ use serde_json::Value;
 
 #[derive(Deserialize)]
 pub struct QueryResponse {
+    #[allow(dead_code)]
     pub avatar: String,
     pub proofs: Vec<KVSingleProof>,
 }
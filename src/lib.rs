@@ -1,9 +1,20 @@
+/// Synchronous API, driving its own tokio runtime.
+/// Do not call it from inside an async context: blocking on a runtime inside another one panics.
+#[cfg(feature = "blocking")]
+pub mod blocking;
+/// Long-lived client sharing one connection pool across all operations.
+pub mod client;
+/// Load [ClientConfig](client::ClientConfig) from environment variables and config files.
+pub mod config;
 /// KVService: Storage for each ProofService connection record.
 /// See also: [Docs](https://docs.next.id/kv-service/kv-intro)
 pub mod kv_service;
 /// ProofService: Build a trustable connection between 2 identities.
 /// See also: [Docs](https://docs.next.id/proof-service/ps-intro)
 pub mod proof_service;
+/// In-process mock servers for integration tests.
+#[cfg(feature = "testing")]
+pub mod testing;
 /// Types
 pub mod types;
 
@@ -1,16 +1,65 @@
 mod procedure;
+mod search;
+#[cfg(test)]
+mod tests;
 mod types;
 pub use self::types::Action;
 pub use self::types::Platform;
+pub use self::types::{Avatar, Binding, Health, Proof};
 pub use procedure::ProofProcedure;
+pub use search::{Order, Page, Pagination, QueryOptions, SortKey};
 
-use self::types::Avatar;
-use crate::{types::Result, util::http::request};
+use self::types::raw::{exists, query};
+use crate::{
+    config::normalize_base_url,
+    types::{Error, Result},
+    util::{
+        crypto::Secp256k1KeyPair,
+        hex_encode,
+        http::{default_transport, lookup, request, Call, Lookup, Transport, DEFAULT_TIMEOUT},
+        metrics::{MetricsRecorder, Operation, Service},
+    },
+};
+use futures::TryStreamExt;
 use http::Method;
-use hyper::Body;
-use std::borrow::Borrow;
+use std::{
+    borrow::Borrow,
+    collections::HashMap,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 use url::Url;
 
+/// How [Client::find_by_many_with] splits identities into `GET v1/proof` requests.
+///
+/// ProofService documents no limit on the identities of a single query, so the defaults
+/// are conservative client-side choices rather than server limits.
+#[derive(Debug, Clone)]
+pub struct BatchOptions {
+    /// Max identities per request. Default: 50, keeping each response a few pages long.
+    pub max_identities: usize,
+    /// Max length of the URL-encoded, comma-separated `identity` parameter.
+    /// Default: 1500 bytes, well below the 8 KiB request line accepted by common reverse
+    /// proxies (e.g. nginx's default `large_client_header_buffers`).
+    pub max_param_length: usize,
+    /// Max pages fetched per request: records past them are left out. Default: 100.
+    pub max_pages: usize,
+}
+
+impl Default for BatchOptions {
+    fn default() -> Self {
+        Self {
+            max_identities: 50,
+            max_param_length: 1500,
+            max_pages: 100,
+        }
+    }
+}
+
 /// ProofService endpoint
 #[derive(Debug, Clone)]
 pub enum Endpoint {
@@ -25,6 +74,48 @@ pub enum Endpoint {
 }
 
 impl Endpoint {
+    /// Bind this endpoint to a custom [Transport].
+    /// # Examples
+    /// ```rust
+    /// # use std::sync::Arc;
+    /// # use nextid_sdk::{proof_service::Endpoint, util::http::HyperTransport};
+    /// let client = Endpoint::Staging.with_transport(Arc::new(HyperTransport::new()));
+    /// ```
+    pub fn with_transport(&self, transport: Arc<dyn Transport>) -> Client {
+        Client {
+            endpoint: self.clone(),
+            transport,
+            timeout: Some(DEFAULT_TIMEOUT),
+            metrics: None,
+            no_exists_route: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Root URL of the server, without trailing slash.
+    /// # Examples
+    /// ```rust
+    /// # use nextid_sdk::proof_service::Endpoint;
+    /// assert_eq!("https://proof-service.next.id", Endpoint::Production.base_url());
+    /// let custom = Endpoint::Custom("https://proof-service.example.com/".into());
+    /// assert_eq!("https://proof-service.example.com", custom.base_url());
+    /// ```
+    pub fn base_url(&self) -> String {
+        match self {
+            Endpoint::Production => "https://proof-service.next.id".to_string(),
+            Endpoint::Staging => "https://proof-service.nextnext.id".to_string(),
+            Endpoint::Custom(url) => url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// Label of this endpoint variant in metrics.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Endpoint::Production => "production",
+            Endpoint::Staging => "staging",
+            Endpoint::Custom(_) => "custom",
+        }
+    }
+
     /// Fetch records by given `platform` and `identity`.
     /// If `fetch_all == true`, fetch all records till pagination ends.
     /// If not, only fetch first page of the results.
@@ -32,8 +123,12 @@ impl Endpoint {
     /// ```rust
     /// # #[tokio::main]
     /// # async fn main() {
+    /// # use std::sync::Arc;
     /// # use nextid_sdk::proof_service::{Endpoint, Platform};
-    /// let avatars = Endpoint::Staging.find_by(Platform::Twitter, "yeiwb", false).await.unwrap();
+    /// # use nextid_sdk::util::cassette::ReplayTransport;
+    /// # let replay = ReplayTransport::from_file("fixtures/proof_service.json").unwrap();
+    /// # let endpoint = Endpoint::Staging.with_transport(Arc::new(replay));
+    /// let avatars = endpoint.find_by(Platform::Twitter, "yeiwb", false).await.unwrap();
     /// # assert!(avatars.len() > 0)
     /// # }
     /// ```
@@ -43,40 +138,117 @@ impl Endpoint {
         identity: &str,
         fetch_all: bool,
     ) -> Result<Vec<Avatar>> {
-        let mut result: Vec<Avatar> = vec![];
-        let mut page: usize = 1;
-        loop {
-            let single_page = self
-                .find_by_single_page(&platform.to_string(), identity, page)
-                .await?;
-            single_page.ids.into_iter().for_each(|avatar| {
-                result.push(avatar.into());
-            });
-            if !fetch_all || (single_page.pagination.next == 0) {
-                break;
-            }
-            page += 1;
-        }
+        self.with_transport(default_transport())
+            .find_by(platform, identity, fetch_all)
+            .await
+    }
 
-        Ok(result)
+    /// Fetch the avatar record of `avatar` with all its proofs, looked up on the `nextid` platform.
+    /// Compressed and uncompressed public keys give the same result. `None` if it has no proof.
+    /// # Examples
+    /// ```rust
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// # use std::sync::Arc;
+    /// # use nextid_sdk::proof_service::Endpoint;
+    /// # use nextid_sdk::util::{cassette::ReplayTransport, crypto::Secp256k1KeyPair};
+    /// # let replay = ReplayTransport::from_file("fixtures/proof_service.json").unwrap();
+    /// # let endpoint = Endpoint::Staging.with_transport(Arc::new(replay));
+    /// let avatar = Secp256k1KeyPair::from_pk_hex("0x040d2ee3a597c24c66717dba01d7d14cb55e307834fe23428bd85c64249111f08a82a46270878006a794acb7d06eb4d5353547119cfee56f087a52fb3c0971e8e4").unwrap();
+    /// let found = endpoint.find_by_avatar(&avatar).await.unwrap().unwrap();
+    /// assert_eq!("yeiwb", found.proofs[0].identity);
+    /// # }
+    /// ```
+    pub async fn find_by_avatar(&self, avatar: &Secp256k1KeyPair) -> Result<Option<Avatar>> {
+        self.with_transport(default_transport())
+            .find_by_avatar(avatar)
+            .await
     }
 
-    /// Find a record by given platform and identity.
-    async fn find_by_single_page(
+    /// Tell whether `identity` on `platform` is bound to `avatar`, and if its proof is valid.
+    /// Asks `GET v1/proof/exists`, or looks the avatar up on servers without this route,
+    /// which the client remembers.
+    /// # Examples
+    /// ```rust
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// # use std::sync::Arc;
+    /// # use nextid_sdk::proof_service::{Binding, Endpoint, Platform};
+    /// # use nextid_sdk::util::{cassette::ReplayTransport, crypto::Secp256k1KeyPair};
+    /// # let replay = ReplayTransport::from_file("fixtures/proof_service.json").unwrap();
+    /// # let endpoint = Endpoint::Staging.with_transport(Arc::new(replay));
+    /// let avatar = Secp256k1KeyPair::from_pk_hex("0x020d2ee3a597c24c66717dba01d7d14cb55e307834fe23428bd85c64249111f08a").unwrap();
+    /// let binding = endpoint.exists(Platform::Twitter, "yeiwb", &avatar).await.unwrap();
+    /// assert_eq!(Binding::Valid, binding);
+    /// # }
+    /// ```
+    pub async fn exists(
         &self,
-        platform: &str,
+        platform: Platform,
         identity: &str,
-        page: usize,
-    ) -> Result<types::raw::query::Response> {
-        let uri = self.uri(
-            "v1/proof",
-            &[
-                ("platform", platform),
-                ("identity", identity),
-                ("page", &page.to_string()),
-            ],
-        )?;
-        request(Method::GET, &uri, Body::empty()).await
+        avatar: &Secp256k1KeyPair,
+    ) -> Result<Binding> {
+        self.with_transport(default_transport())
+            .exists(platform, identity, avatar)
+            .await
+    }
+
+    /// Fetch records of many identities on `platform` at once, keyed by identity as given.
+    /// Identities are batched into as few requests as [BatchOptions::default()] allows,
+    /// and every page is fetched. Identities without any record map to an empty `Vec`.
+    /// Identities containing a comma are rejected: the server splits them.
+    /// # Examples
+    /// ```rust
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// # use std::sync::Arc;
+    /// # use nextid_sdk::proof_service::{Endpoint, Platform};
+    /// # use nextid_sdk::util::cassette::ReplayTransport;
+    /// # let replay = ReplayTransport::from_file("fixtures/proof_service.json").unwrap();
+    /// # let endpoint = Endpoint::Staging.with_transport(Arc::new(replay));
+    /// let avatars = endpoint.find_by_many(Platform::Twitter, &["yeiwb", "nobody_here"]).await.unwrap();
+    /// assert_eq!(1, avatars["yeiwb"].len());
+    /// assert!(avatars["nobody_here"].is_empty());
+    /// # }
+    /// ```
+    pub async fn find_by_many<I: AsRef<str>>(
+        &self,
+        platform: Platform,
+        identities: &[I],
+    ) -> Result<HashMap<String, Vec<Avatar>>> {
+        self.with_transport(default_transport())
+            .find_by_many(platform, identities)
+            .await
+    }
+
+    /// Same as [Endpoint::find_by_many], batching identities according to `options`.
+    pub async fn find_by_many_with<I: AsRef<str>>(
+        &self,
+        platform: Platform,
+        identities: &[I],
+        options: &BatchOptions,
+    ) -> Result<HashMap<String, Vec<Avatar>>> {
+        self.with_transport(default_transport())
+            .find_by_many_with(platform, identities, options)
+            .await
+    }
+
+    /// Check the server is up, and get what it reports about itself.
+    /// # Examples
+    /// ```rust
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// # use std::sync::Arc;
+    /// # use nextid_sdk::proof_service::{Endpoint, Platform};
+    /// # use nextid_sdk::util::cassette::ReplayTransport;
+    /// # let replay = ReplayTransport::from_file("fixtures/proof_service.json").unwrap();
+    /// # let endpoint = Endpoint::Staging.with_transport(Arc::new(replay));
+    /// let health = endpoint.healthz().await.unwrap();
+    /// assert!(health.supported_platforms().contains(&Platform::Twitter));
+    /// # }
+    /// ```
+    pub async fn healthz(&self) -> Result<Health> {
+        self.with_transport(default_transport()).healthz().await
     }
 
     /// Concat server API URL.
@@ -87,12 +259,358 @@ impl Endpoint {
         K: AsRef<str>,
         V: AsRef<str>,
     {
-        use Endpoint::*;
-        let base = match self {
-            Production => format!("https://proof-service.next.id/{}", path),
-            Staging => format!("https://proof-service.nextnext.id/{}", path),
-            Custom(url) => format!("{}/{}", url, path),
-        };
+        let base = format!("{}/{}", self.base_url(), path);
         Url::parse_with_params(&base, query).map_err(|e| e.into())
     }
 }
+
+/// Parse `production`, `staging` or the root URL of a custom server.
+/// URLs must be `http` / `https` without query nor fragment, and are normalized
+/// (e.g. lowercase host, no trailing slash).
+/// # Examples
+/// ```rust
+/// # use nextid_sdk::proof_service::Endpoint;
+/// let endpoint: Endpoint = "https://proof-service.EXAMPLE.com/".parse().unwrap();
+/// assert_eq!("https://proof-service.example.com", endpoint.base_url());
+/// assert!("ftp://proof-service.example.com".parse::<Endpoint>().is_err());
+/// ```
+impl FromStr for Endpoint {
+    type Err = Error;
+
+    fn from_str(endpoint: &str) -> Result<Self> {
+        match endpoint.trim().to_ascii_lowercase().as_str() {
+            "production" => Ok(Endpoint::Production),
+            "staging" => Ok(Endpoint::Staging),
+            _ => Ok(Endpoint::Custom(normalize_base_url(endpoint)?)),
+        }
+    }
+}
+
+/// ProofService [Endpoint] bound to a [Transport].
+#[derive(Clone)]
+pub struct Client {
+    pub endpoint: Endpoint,
+    pub transport: Arc<dyn Transport>,
+    /// Deadline of each API call, retries included. `None` waits forever.
+    pub timeout: Option<Duration>,
+    /// Receives metrics of each API call.
+    pub metrics: Option<Arc<dyn MetricsRecorder>>,
+    /// Set once the server turned out not to serve `GET v1/proof/exists`.
+    no_exists_route: Arc<AtomicBool>,
+}
+
+impl From<Endpoint> for Client {
+    fn from(endpoint: Endpoint) -> Self {
+        endpoint.with_transport(default_transport())
+    }
+}
+
+impl Client {
+    /// Same client, but with another deadline for each API call.
+    /// The TCP connect timeout is not affected: it belongs to the transport
+    /// (see [HttpConfig::connect_timeout](crate::util::http::HttpConfig::connect_timeout)).
+    /// # Examples
+    /// ```rust
+    /// # use std::time::Duration;
+    /// # use nextid_sdk::proof_service::Endpoint;
+    /// let client = Endpoint::Staging.with_transport(nextid_sdk::util::http::default_transport());
+    /// let impatient = client.with_timeout(Duration::from_secs(1));
+    /// ```
+    pub fn with_timeout(&self, timeout: Duration) -> Self {
+        Self {
+            timeout: Some(timeout),
+            ..self.clone()
+        }
+    }
+
+    /// Same client, reporting metrics of each API call to `metrics`.
+    pub fn with_metrics(&self, metrics: Arc<dyn MetricsRecorder>) -> Self {
+        Self {
+            metrics: Some(metrics),
+            ..self.clone()
+        }
+    }
+
+    /// Settings of a single API call of `operation`.
+    fn call(&self, operation: &'static str) -> Call<'_> {
+        Call {
+            transport: self.transport.as_ref(),
+            operation: Operation {
+                service: Service::ProofService,
+                endpoint: self.endpoint.label(),
+                name: operation,
+            },
+            timeout: self.timeout,
+            metrics: self.metrics.as_deref(),
+        }
+    }
+
+    /// Same as [Endpoint::find_by], but requests through `self.transport`.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "ProofService.find_by",
+            skip_all,
+            fields(%platform, identity, fetch_all)
+        )
+    )]
+    pub async fn find_by(
+        &self,
+        platform: Platform,
+        identity: &str,
+        fetch_all: bool,
+    ) -> Result<Vec<Avatar>> {
+        let mut options = QueryOptions::new();
+        if !fetch_all {
+            options = options.max_pages(1);
+        }
+        search::dedup(self.pages("find_by", platform, identity, options))
+            .try_collect()
+            .await
+    }
+
+    /// Same as [Endpoint::find_by_avatar], but requests through `self.transport`.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "ProofService.find_by_avatar",
+            skip_all,
+            fields(avatar = %hex_encode(&avatar.pk.serialize_compressed()))
+        )
+    )]
+    pub async fn find_by_avatar(&self, avatar: &Secp256k1KeyPair) -> Result<Option<Avatar>> {
+        self.avatar_record("find_by_avatar", avatar).await
+    }
+
+    /// Same as [Endpoint::exists], but requests through `self.transport`.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "ProofService.exists",
+            skip_all,
+            fields(%platform, identity, avatar = %hex_encode(&avatar.pk.serialize_compressed()))
+        )
+    )]
+    pub async fn exists(
+        &self,
+        platform: Platform,
+        identity: &str,
+        avatar: &Secp256k1KeyPair,
+    ) -> Result<Binding> {
+        if !self.no_exists_route.load(Ordering::Relaxed) {
+            let public_key = format!("0x{}", hex_encode(&avatar.pk.serialize_compressed()));
+            let uri = self.endpoint.uri(
+                "v1/proof/exists",
+                vec![
+                    ("platform", platform.to_string()),
+                    ("identity", identity.to_string()),
+                    ("public_key", public_key),
+                ],
+            )?;
+            let call = self.call("exists");
+            match lookup::<exists::Response>(call, Method::GET, &uri, vec![]).await? {
+                Lookup::Found(found) => return Ok(found.into()),
+                Lookup::NotFound => return Ok(Binding::NotBound),
+                Lookup::NoRoute => {
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!("no exists route, looking the avatar up instead");
+                    self.no_exists_route.store(true, Ordering::Relaxed);
+                }
+            }
+        }
+
+        let key = identity_key(platform, identity);
+        let binding = self
+            .avatar_record("exists", avatar)
+            .await?
+            .and_then(|found| {
+                found
+                    .proofs
+                    .iter()
+                    .find(|proof| {
+                        proof.platform == platform && identity_key(platform, &proof.identity) == key
+                    })
+                    .map(Binding::from)
+            });
+        Ok(binding.unwrap_or(Binding::NotBound))
+    }
+
+    /// Same as [Endpoint::find_by_many], but requests through `self.transport`.
+    pub async fn find_by_many<I: AsRef<str>>(
+        &self,
+        platform: Platform,
+        identities: &[I],
+    ) -> Result<HashMap<String, Vec<Avatar>>> {
+        self.find_by_many_with(platform, identities, &BatchOptions::default())
+            .await
+    }
+
+    /// Same as [Endpoint::find_by_many_with], but requests through `self.transport`.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "ProofService.find_by_many",
+            skip_all,
+            fields(%platform, identities = identities.len())
+        )
+    )]
+    pub async fn find_by_many_with<I: AsRef<str>>(
+        &self,
+        platform: Platform,
+        identities: &[I],
+        options: &BatchOptions,
+    ) -> Result<HashMap<String, Vec<Avatar>>> {
+        let mut result: HashMap<String, Vec<Avatar>> = HashMap::new();
+        // Identities as given, by the form the server answers with.
+        let mut requested: HashMap<String, Vec<String>> = HashMap::new();
+        let mut unique: Vec<String> = vec![];
+        for identity in identities {
+            let identity = identity.as_ref();
+            if identity.contains(',') {
+                return Err(Error::ValidationError(format!(
+                    "identity {:?} contains a comma, which separates identities of a batch",
+                    identity
+                )));
+            }
+            result.insert(identity.to_string(), vec![]);
+            let key = identity_key(platform, identity);
+            let given = requested.entry(key).or_insert_with(|| {
+                unique.push(identity.to_string());
+                vec![]
+            });
+            given.push(identity.to_string());
+        }
+
+        let max_pages = options.max_pages.max(1);
+        for batch in batches(unique, options) {
+            let mut page: usize = 1;
+            for fetched in 1..=max_pages {
+                let query = QueryOptions::new().request(platform, batch.clone(), page);
+                let single_page = self.find_by_single_page("find_by_many", &query).await?;
+                for avatar in single_page.ids {
+                    let avatar = Avatar::try_from(avatar)?;
+                    for key in avatar_keys(platform, &avatar) {
+                        for identity in requested.get(&key).into_iter().flatten() {
+                            let avatars = result.get_mut(identity).unwrap();
+                            // Avatars may shift to the next page between requests.
+                            if !avatars.iter().any(|known| known.avatar == avatar.avatar) {
+                                avatars.push(avatar.clone());
+                            }
+                        }
+                    }
+                }
+                // `0` on the last page. Never go back, in case the server misbehaves.
+                let next = single_page.pagination.next;
+                if next <= page {
+                    break;
+                }
+                if fetched == max_pages {
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!(max_pages, "page limit reached");
+                    break;
+                }
+                page = next;
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Record of `avatar`, looked up on the `nextid` platform.
+    async fn avatar_record(
+        &self,
+        operation: &'static str,
+        avatar: &Secp256k1KeyPair,
+    ) -> Result<Option<Avatar>> {
+        let key = hex_encode(&avatar.pk.serialize_compressed());
+        let query = QueryOptions::new().request(Platform::NextID, vec![format!("0x{}", key)], 1);
+        let page = self.find_by_single_page(operation, &query).await?;
+        for found in page.ids {
+            let found = Avatar::try_from(found)?;
+            if avatar_keys(Platform::NextID, &found).contains(&key) {
+                return Ok(Some(found));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Fetch a single page of records.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "ProofService.find_by_single_page",
+            skip_all,
+            fields(platform = %query.platform, identities = query.identity.len(), page = query.page)
+        )
+    )]
+    async fn find_by_single_page(
+        &self,
+        operation: &'static str,
+        query: &query::Request,
+    ) -> Result<query::Response> {
+        let uri = self.endpoint.uri("v1/proof", query.query_pairs())?;
+        request(self.call(operation), Method::GET, &uri, vec![]).await
+    }
+
+    /// Same as [Endpoint::healthz], but requests through `self.transport`.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "ProofService.healthz", skip_all)
+    )]
+    pub async fn healthz(&self) -> Result<Health> {
+        let uri = self
+            .endpoint
+            .uri::<Vec<(String, String)>, _, _>("healthz", vec![])?;
+        request(self.call("healthz"), Method::GET, &uri, vec![]).await
+    }
+}
+
+/// Form of `identity` the server answers with: lowercase, or compressed public key for `nextid`.
+fn identity_key(platform: Platform, identity: &str) -> String {
+    match Secp256k1KeyPair::from_pk_hex(identity) {
+        Ok(avatar) if platform == Platform::NextID => hex_encode(&avatar.pk.serialize_compressed()),
+        _ => identity.to_lowercase(),
+    }
+}
+
+/// Keys of the requested identities `avatar` is a record of.
+fn avatar_keys(platform: Platform, avatar: &Avatar) -> Vec<String> {
+    if platform == Platform::NextID {
+        return Secp256k1KeyPair::from_pk_vec(&avatar.avatar)
+            .map(|avatar| vec![hex_encode(&avatar.pk.serialize_compressed())])
+            .unwrap_or_default();
+    }
+    avatar
+        .proofs
+        .iter()
+        .filter(|proof| proof.platform == platform)
+        .map(|proof| proof.identity.to_lowercase())
+        .collect()
+}
+
+/// Split `identities` into batches of at most [BatchOptions::max_identities],
+/// each of them fitting in [BatchOptions::max_param_length] once URL-encoded.
+fn batches(identities: Vec<String>, options: &BatchOptions) -> Vec<Vec<String>> {
+    let mut batches: Vec<Vec<String>> = vec![];
+    let mut length = 0;
+    for identity in identities {
+        let encoded: usize = url::form_urlencoded::byte_serialize(identity.as_bytes())
+            .map(str::len)
+            .sum();
+        match batches.last_mut() {
+            // A comma is encoded as `%2C`.
+            Some(batch)
+                if batch.len() < options.max_identities
+                    && length + 3 + encoded <= options.max_param_length =>
+            {
+                length += 3 + encoded;
+                batch.push(identity);
+            }
+            _ => {
+                length = encoded;
+                batches.push(vec![identity]);
+            }
+        }
+    }
+    batches
+}
@@ -8,14 +8,17 @@ use super::{
 use crate::{
     types::{Error, Result},
     util::{
-        self, base64_encode, crypto::Secp256k1KeyPair, eth_address_from_public_key, hex_decode,
-        hex_encode, http::request,
+        self, base64_encode,
+        crypto::Secp256k1KeyPair,
+        eth_address_from_public_key, hex_decode, hex_encode,
+        http::{default_transport, request, Call, Transport, DEFAULT_TIMEOUT},
+        metrics::{MetricsRecorder, Operation, Service},
     },
 };
 use chrono::NaiveDateTime;
 use http::Method;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::Arc, time::Duration};
 
 #[derive(Serialize, Deserialize)]
 pub struct ProofPayloadExtra {
@@ -27,6 +30,11 @@ pub struct ProofPayloadExtra {
 /// ProofChain modification procedure instance.
 pub struct ProofProcedure {
     pub endpoint: Endpoint,
+    pub transport: Arc<dyn Transport>,
+    /// Deadline of each request, retries included. `None` waits forever.
+    pub timeout: Option<Duration>,
+    /// Receives metrics of each request.
+    pub metrics: Option<Arc<dyn MetricsRecorder>>,
     pub action: Action,
     pub avatar: Secp256k1KeyPair,
     pub platform: Platform,
@@ -65,6 +73,9 @@ impl ProofProcedure {
     ) -> Self {
         Self {
             endpoint,
+            transport: default_transport(),
+            timeout: Some(DEFAULT_TIMEOUT),
+            metrics: None,
             action,
             avatar,
             platform,
@@ -79,6 +90,38 @@ impl ProofProcedure {
         }
     }
 
+    /// Send all requests of this procedure through `transport`.
+    pub fn with_transport(mut self, transport: Arc<dyn Transport>) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Give up each request of this procedure after `timeout`.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Report metrics of each request of this procedure to `metrics`.
+    pub fn with_metrics(mut self, metrics: Arc<dyn MetricsRecorder>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Settings of a single request of `operation`.
+    fn call(&self, operation: &'static str) -> Call<'_> {
+        Call {
+            transport: self.transport.as_ref(),
+            operation: Operation {
+                service: Service::ProofService,
+                endpoint: self.endpoint.label(),
+                name: operation,
+            },
+            timeout: self.timeout,
+            metrics: self.metrics.as_deref(),
+        }
+    }
+
     /// Request for signature payloads and post content from ProofService.
     /// Will fill `self`'s `sign_payload`, `post_content`, `uuid` and `created_at`.
     /// # Examples
@@ -88,13 +131,24 @@ impl ProofProcedure {
     /// # use nextid_sdk::proof_service::ProofProcedure;
     /// # use nextid_sdk::proof_service::{Endpoint, Action, Platform};
     /// # use nextid_sdk::util::crypto::Secp256k1KeyPair;
-    /// # let mut rng = rand::rngs::OsRng;
-    /// # let avatar = Secp256k1KeyPair::generate(&mut rng);
-    /// let mut procedure = ProofProcedure::new(Endpoint::Staging, Action::Create, avatar, Platform::Twitter, "example");
+    /// # use nextid_sdk::util::cassette::ReplayTransport;
+    /// # let avatar = Secp256k1KeyPair::from_pk_hex("0x047e55e1b78e873c6f7d585064b41cd2735000bacc0092fe947c11ab7742ed351fef59c4f5d558d14a031bb09e44877f9e61f89993f895eb8fa6cfaafe74f6f55c").unwrap();
+    /// # let replay = ReplayTransport::from_file("fixtures/proof_service.json").unwrap();
+    /// let mut procedure = ProofProcedure::new(Endpoint::Staging, Action::Create, avatar, Platform::Twitter, "example")
+    /// #   .with_transport(std::sync::Arc::new(replay))
+    /// ;
     /// assert_eq!((), procedure.get_payload().await.unwrap());
     /// # assert!(procedure.sign_payload.unwrap().len() > 0)
     /// # }
     /// ```
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "ProofProcedure.get_payload",
+            skip_all,
+            fields(action = %self.action, platform = %self.platform, identity = %self.identity)
+        )
+    )]
     pub async fn get_payload(&mut self) -> Result<()> {
         let url = self
             .endpoint
@@ -107,9 +161,10 @@ impl ProofProcedure {
             extra: None,
         };
         let response: PayloadResponse = request(
+            self.call("payload"),
             Method::POST,
             &url,
-            serde_json::to_vec(&request_body)?.into(),
+            serde_json::to_vec(&request_body)?,
         )
         .await?;
 
@@ -125,6 +180,14 @@ impl ProofProcedure {
     /// If `self.platform == Ethereum && self.action == Create`, `avatar_signature` and `ethereum_signature` must both be provided.
     /// If `self.platform == Ethereum && self.action == Delete`, either of `avatar_signature` or `ethereum_signature` should be provided.
     /// Otherwise, leave these `None`.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "ProofProcedure.submit",
+            skip_all,
+            fields(action = %self.action, platform = %self.platform, identity = %self.identity, %proof_location)
+        )
+    )]
     pub async fn submit(
         &mut self,
         proof_location: String,
@@ -151,14 +214,16 @@ impl ProofProcedure {
             created_at: self
                 .created_at
                 .expect("creatd_at must be available at this moment.")
+                .and_utc()
                 .timestamp()
                 .to_string(),
             extra: upload_extra,
         };
         request::<UploadResponse>(
+            self.call("submit"),
             Method::POST,
             &url,
-            serde_json::to_vec(&request_body)?.into(),
+            serde_json::to_vec(&request_body)?,
         )
         .await?;
 
@@ -203,14 +268,14 @@ impl ProofProcedure {
                 .extra
                 .as_ref()
                 .map(|e| base64_encode(&e.ethereum_wallet_signature)),
-            signature: self.signature.as_ref().map(|sig| base64_encode(sig)),
+            signature: self.signature.as_ref().map(base64_encode),
         })
     }
 
     /// Validate avatar signature.
     fn local_validate_avatar_sig(&self, avatar_signature: Option<&Vec<u8>>) -> Result<()> {
         if avatar_signature.is_none() {
-            return Err(Error::ServerError(
+            return Err(Error::ValidationError(
                 "ProofProcedure.local_validate_avatar_sig(): Avatar signature required.".into(),
             ));
         }
@@ -220,7 +285,7 @@ impl ProofProcedure {
             self.sign_payload.as_ref().unwrap(),
         )?;
         if recovered.pk != self.avatar.pk {
-            Err(Error::ServerError(
+            Err(Error::ValidationError(
                 "ProofProcedure.local_validate_avatar_sig(): Pubkey recovered from signature mismatches `self.avatar`.".into(),
             ))
         } else {
@@ -231,7 +296,7 @@ impl ProofProcedure {
     /// Validate ethereum signature.
     fn local_validate_eth_sig(&self, ethereum_signature: Option<&Vec<u8>>) -> Result<()> {
         if ethereum_signature.is_none() {
-            return Err(Error::ServerError(
+            return Err(Error::ValidationError(
                 "ProofProcedure.local_validate_eth_sig(): Ethereum wallet signature required."
                     .into(),
             ));
@@ -239,15 +304,16 @@ impl ProofProcedure {
 
         let eth_sig = ethereum_signature.unwrap();
         let recovered = Secp256k1KeyPair::recover_from_personal_signature(
-            &eth_sig,
+            eth_sig,
             &self.sign_payload.clone().unwrap(),
         )?;
         let expected_address = hex_decode(&self.identity)?;
         let recovered_address: Vec<u8> = eth_address_from_public_key(&recovered.pk).into();
         if expected_address != recovered_address {
-            Err(Error::ServerError(format!(
+            Err(Error::ValidationError(
                 "ProofProcedure.local_validate_eth_sig(): Ethereum address and signatures mismatch."
-            )))
+                    .into(),
+            ))
         } else {
             Ok(())
         }
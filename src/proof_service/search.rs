@@ -0,0 +1,476 @@
+use super::{query, Avatar, Client, Endpoint, Platform};
+use crate::{
+    types::{Error, Result},
+    util::http::default_transport,
+};
+use futures::{
+    future::{self, BoxFuture},
+    stream::{self, FuturesOrdered},
+    Stream, StreamExt, TryStreamExt,
+};
+use std::collections::HashSet;
+
+/// Pagination of a ProofService query result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pagination {
+    /// Records matching the query, all pages included.
+    pub total: u64,
+    /// Records per page.
+    pub per: usize,
+    /// Number of this page, starting from 1.
+    pub current: usize,
+    /// Number of the next page, `None` on the last one.
+    pub next: Option<usize>,
+}
+
+impl Pagination {
+    /// Number of pages of the whole result.
+    pub fn pages(&self) -> u64 {
+        if self.per == 0 {
+            return 0;
+        }
+        self.total.div_ceil(self.per as u64)
+    }
+}
+
+impl From<query::Pagination> for Pagination {
+    fn from(raw: query::Pagination) -> Self {
+        Self {
+            total: raw.total,
+            per: raw.per,
+            current: raw.current,
+            next: (raw.next != 0).then_some(raw.next),
+        }
+    }
+}
+
+/// A page of query results.
+#[derive(Clone)]
+pub struct Page {
+    pub avatars: Vec<Avatar>,
+    pub pagination: Pagination,
+}
+
+impl TryFrom<query::Response> for Page {
+    type Error = Error;
+
+    fn try_from(raw: query::Response) -> Result<Self> {
+        Ok(Self {
+            avatars: raw
+                .ids
+                .into_iter()
+                .map(Avatar::try_from)
+                .collect::<Result<_>>()?,
+            pagination: raw.pagination.into(),
+        })
+    }
+}
+
+/// Field ProofService sorts query results by.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SortKey {
+    /// When the avatar got its first proof.
+    ActivatedAt,
+    /// When the matched proof was created.
+    CreatedAt,
+    /// Any other key the server accepts.
+    Other(String),
+}
+
+impl SortKey {
+    fn as_str(&self) -> &str {
+        match self {
+            SortKey::ActivatedAt => "activated_at",
+            SortKey::CreatedAt => "created_at",
+            SortKey::Other(key) => key,
+        }
+    }
+}
+
+/// Sort order of query results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Order {
+    Ascending,
+    Descending,
+}
+
+impl Order {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Order::Ascending => "asc",
+            Order::Descending => "desc",
+        }
+    }
+}
+
+/// Options of paginated ProofService queries.
+///
+/// Server-side options (`exact`, `sort`, `order`, `page_size`) are left to the server's defaults
+/// unless set. Client-side filters (`only_valid_proofs`, `with_platform`) drop records from each
+/// page after it is received: [Pagination] still counts them.
+/// # Examples
+/// ```rust
+/// # use nextid_sdk::proof_service::{Order, Platform, QueryOptions, SortKey};
+/// // Pages 3 and 4 only.
+/// let options = QueryOptions::new().start_page(3).max_pages(2);
+/// // Newest first, only avatars also holding a valid GitHub proof.
+/// let options = QueryOptions::new()
+///     .exact(true)
+///     .sort(SortKey::ActivatedAt)
+///     .order(Order::Descending)
+///     .only_valid_proofs()
+///     .with_platform(Platform::Github);
+/// ```
+#[derive(Debug, Clone)]
+pub struct QueryOptions {
+    pub(crate) start_page: usize,
+    pub(crate) max_pages: Option<usize>,
+    pub(crate) concurrency: usize,
+    pub(crate) exact: Option<bool>,
+    pub(crate) sort: Option<SortKey>,
+    pub(crate) order: Option<Order>,
+    pub(crate) page_size: Option<usize>,
+    pub(crate) only_valid_proofs: bool,
+    pub(crate) platforms: Vec<Platform>,
+}
+
+impl Default for QueryOptions {
+    fn default() -> Self {
+        Self {
+            start_page: 1,
+            max_pages: None,
+            concurrency: 1,
+            exact: None,
+            sort: None,
+            order: None,
+            page_size: None,
+            only_valid_proofs: false,
+            platforms: vec![],
+        }
+    }
+}
+
+impl QueryOptions {
+    /// Every page, from the first one.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start from page `page` (starting from 1).
+    pub fn start_page(mut self, page: usize) -> Self {
+        self.start_page = page.max(1);
+        self
+    }
+
+    /// Stop after `max` pages.
+    pub fn max_pages(mut self, max: usize) -> Self {
+        self.max_pages = Some(max);
+        self
+    }
+
+    /// Once the first page tells how many there are, fetch up to `concurrency` of the next pages
+    /// at a time. Pages are still yielded in order. `1` (default) fetches them one by one.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Match `identity` exactly (`true`) or fuzzily (`false`).
+    pub fn exact(mut self, exact: bool) -> Self {
+        self.exact = Some(exact);
+        self
+    }
+
+    /// Sort results by `key`.
+    pub fn sort(mut self, key: SortKey) -> Self {
+        self.sort = Some(key);
+        self
+    }
+
+    /// Sort results in `order`.
+    pub fn order(mut self, order: Order) -> Self {
+        self.order = Some(order);
+        self
+    }
+
+    /// Ask for `size` records per page. Servers not supporting it keep their own page size:
+    /// [Pagination::per] tells the actual one.
+    pub fn page_size(mut self, size: usize) -> Self {
+        self.page_size = Some(size.max(1));
+        self
+    }
+
+    /// Drop proofs not marked valid by the server, and avatars left without any proof.
+    pub fn only_valid_proofs(mut self) -> Self {
+        self.only_valid_proofs = true;
+        self
+    }
+
+    /// Only keep avatars holding a proof on `platform`.
+    /// Call it several times to require several platforms.
+    pub fn with_platform(mut self, platform: Platform) -> Self {
+        self.platforms.push(platform);
+        self
+    }
+
+    /// `GET v1/proof` query of `page` with the server-side options.
+    pub(crate) fn request(
+        &self,
+        platform: Platform,
+        identity: Vec<String>,
+        page: usize,
+    ) -> query::Request {
+        query::Request {
+            platform,
+            identity,
+            page: Some(page),
+            exact: self.exact,
+            sort: self.sort.as_ref().map(|key| key.as_str().to_string()),
+            order: self.order.map(|order| order.as_str().to_string()),
+            size: self.page_size,
+        }
+    }
+
+    /// Apply the client-side filters to `avatars`.
+    pub(crate) fn filter(&self, avatars: Vec<Avatar>) -> Vec<Avatar> {
+        avatars
+            .into_iter()
+            .filter_map(|mut avatar| {
+                if self.only_valid_proofs {
+                    avatar.proofs.retain(|proof| proof.is_valid);
+                    if avatar.proofs.is_empty() {
+                        return None;
+                    }
+                }
+                self.platforms
+                    .iter()
+                    .all(|platform| {
+                        avatar
+                            .proofs
+                            .iter()
+                            .any(|proof| proof.platform == *platform)
+                    })
+                    .then_some(avatar)
+            })
+            .collect()
+    }
+}
+
+impl Endpoint {
+    /// Stream pages of records by given `platform` and `identity`, fetching each one when polled.
+    /// # Examples
+    /// ```rust
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// # use std::sync::Arc;
+    /// # use futures::TryStreamExt;
+    /// # use nextid_sdk::proof_service::{Endpoint, Platform, QueryOptions};
+    /// # use nextid_sdk::util::cassette::ReplayTransport;
+    /// # let replay = ReplayTransport::from_file("fixtures/proof_service.json").unwrap();
+    /// # let endpoint = Endpoint::Staging.with_transport(Arc::new(replay));
+    /// let mut pages = Box::pin(endpoint.find_by_pages(Platform::Twitter, "yeiwb", QueryOptions::new()));
+    /// while let Some(page) = pages.try_next().await.unwrap() {
+    ///     println!("page {} of {}", page.pagination.current, page.pagination.pages());
+    /// }
+    /// # }
+    /// ```
+    pub fn find_by_pages(
+        &self,
+        platform: Platform,
+        identity: &str,
+        options: QueryOptions,
+    ) -> impl Stream<Item = Result<Page>> + Send + 'static {
+        self.with_transport(default_transport())
+            .find_by_pages(platform, identity, options)
+    }
+
+    /// Stream records by given `platform` and `identity`, fetching pages when needed.
+    /// Avatars already yielded from a previous page are skipped.
+    /// # Examples
+    /// ```rust
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// # use std::sync::Arc;
+    /// # use futures::TryStreamExt;
+    /// # use nextid_sdk::proof_service::{Endpoint, Platform, QueryOptions};
+    /// # use nextid_sdk::util::cassette::ReplayTransport;
+    /// # let replay = ReplayTransport::from_file("fixtures/proof_service.json").unwrap();
+    /// # let endpoint = Endpoint::Staging.with_transport(Arc::new(replay));
+    /// let mut avatars = Box::pin(endpoint.find_by_stream(Platform::Twitter, "yeiwb", QueryOptions::new()));
+    /// while let Some(avatar) = avatars.try_next().await.unwrap() {
+    ///     assert!(!avatar.proofs.is_empty());
+    /// }
+    /// # }
+    /// ```
+    pub fn find_by_stream(
+        &self,
+        platform: Platform,
+        identity: &str,
+        options: QueryOptions,
+    ) -> impl Stream<Item = Result<Avatar>> + Send + 'static {
+        self.with_transport(default_transport())
+            .find_by_stream(platform, identity, options)
+    }
+
+    /// Fetch records by given `platform` and `identity` into a `Vec`, following `options`:
+    /// unlike [Endpoint::find_by], pages can be fetched concurrently.
+    /// Avatars showing up on several pages (e.g. shifted by a new binding) are only kept once.
+    /// # Examples
+    /// ```rust
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// # use std::sync::Arc;
+    /// # use nextid_sdk::proof_service::{Endpoint, Platform, QueryOptions};
+    /// # use nextid_sdk::util::cassette::ReplayTransport;
+    /// # let replay = ReplayTransport::from_file("fixtures/proof_service.json").unwrap();
+    /// # let endpoint = Endpoint::Staging.with_transport(Arc::new(replay));
+    /// let options = QueryOptions::new().concurrency(4);
+    /// let avatars = endpoint.find_by_with(Platform::Twitter, "yeiwb", options).await.unwrap();
+    /// # assert_eq!(1, avatars.len());
+    /// # }
+    /// ```
+    pub async fn find_by_with(
+        &self,
+        platform: Platform,
+        identity: &str,
+        options: QueryOptions,
+    ) -> Result<Vec<Avatar>> {
+        self.with_transport(default_transport())
+            .find_by_with(platform, identity, options)
+            .await
+    }
+}
+
+impl Client {
+    /// Same as [Endpoint::find_by_pages], but requests through `self.transport`.
+    pub fn find_by_pages(
+        &self,
+        platform: Platform,
+        identity: &str,
+        options: QueryOptions,
+    ) -> impl Stream<Item = Result<Page>> + Send + 'static {
+        self.pages("find_by_pages", platform, identity, options)
+    }
+
+    /// Same as [Endpoint::find_by_stream], but requests through `self.transport`.
+    pub fn find_by_stream(
+        &self,
+        platform: Platform,
+        identity: &str,
+        options: QueryOptions,
+    ) -> impl Stream<Item = Result<Avatar>> + Send + 'static {
+        dedup(self.pages("find_by_stream", platform, identity, options))
+    }
+
+    /// Same as [Endpoint::find_by_with], but requests through `self.transport`.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "ProofService.find_by_with",
+            skip_all,
+            fields(%platform, identity, concurrency = options.concurrency)
+        )
+    )]
+    pub async fn find_by_with(
+        &self,
+        platform: Platform,
+        identity: &str,
+        options: QueryOptions,
+    ) -> Result<Vec<Avatar>> {
+        dedup(self.pages("find_by_with", platform, identity, options))
+            .try_collect()
+            .await
+    }
+
+    /// Pages of a query, reported as `operation` in metrics.
+    pub(super) fn pages(
+        &self,
+        operation: &'static str,
+        platform: Platform,
+        identity: &str,
+        options: QueryOptions,
+    ) -> impl Stream<Item = Result<Page>> + Send + 'static {
+        let limit = options
+            .max_pages
+            .map(|max| (options.start_page + max).saturating_sub(1));
+        let prefetch = Prefetch {
+            client: self.clone(),
+            operation,
+            platform,
+            identity: identity.to_string(),
+            next: options.start_page,
+            last: limit.map_or(options.start_page, |limit| limit.min(options.start_page)),
+            limit,
+            options,
+            in_flight: FuturesOrdered::new(),
+        };
+        stream::try_unfold(prefetch, Prefetch::next_page)
+    }
+}
+
+/// Avatars of `pages`, skipping the ones already seen on a previous page.
+pub(super) fn dedup(
+    pages: impl Stream<Item = Result<Page>> + Send + 'static,
+) -> impl Stream<Item = Result<Avatar>> + Send + 'static {
+    let mut seen = HashSet::new();
+    pages
+        .map_ok(|page| stream::iter(page.avatars.into_iter().map(Ok)))
+        .try_flatten()
+        .try_filter(move |avatar| future::ready(seen.insert(avatar.avatar.clone())))
+}
+
+/// Fetches pages ahead of the consumer, at most `concurrency` at a time, yielding them in order.
+struct Prefetch {
+    client: Client,
+    operation: &'static str,
+    platform: Platform,
+    identity: String,
+    options: QueryOptions,
+    /// Next page number to request.
+    next: usize,
+    /// Last page number to request, as far as the pages received so far tell.
+    last: usize,
+    /// Last page number allowed by [QueryOptions::max_pages].
+    limit: Option<usize>,
+    in_flight: FuturesOrdered<BoxFuture<'static, Result<(usize, Page)>>>,
+}
+
+impl Prefetch {
+    async fn next_page(mut self) -> Result<Option<(Page, Self)>> {
+        while self.in_flight.len() < self.options.concurrency && self.next <= self.last {
+            let client = self.client.clone();
+            let operation = self.operation;
+            let number = self.next;
+            let query = self
+                .options
+                .request(self.platform, vec![self.identity.clone()], number);
+            self.in_flight.push_back(Box::pin(async move {
+                let page = client.find_by_single_page(operation, &query).await?;
+                Ok((number, page.try_into()?))
+            }));
+            self.next += 1;
+        }
+        let Some(result) = self.in_flight.next().await else {
+            return Ok(None);
+        };
+        let (number, mut page) = result?;
+
+        // Never walk backwards, whatever the server says.
+        match page.pagination.next.filter(|next| *next > number) {
+            Some(next) => {
+                let reported = usize::try_from(page.pagination.pages()).unwrap_or(usize::MAX);
+                self.last = self.last.max(reported).max(next);
+            }
+            None => {
+                self.last = self.last.min(number);
+                // Pages requested ahead are past the end: drop them.
+                self.in_flight = FuturesOrdered::new();
+            }
+        }
+        if let Some(limit) = self.limit {
+            self.last = self.last.min(limit);
+        }
+        page.avatars = self.options.filter(page.avatars);
+        Ok(Some((page, self)))
+    }
+}
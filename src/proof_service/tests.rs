@@ -0,0 +1,447 @@
+use super::{batches, Avatar, BatchOptions, Binding, Endpoint, Platform, Proof, QueryOptions};
+use crate::{
+    types::{Error, Result},
+    util::{
+        crypto::Secp256k1KeyPair,
+        hex_encode,
+        http::{Transport, TransportRequest, TransportResponse},
+        metrics::{MetricsRecorder, RequestMetrics},
+        ts_to_naive,
+    },
+};
+use futures::StreamExt;
+use http::{header::CONTENT_TYPE, HeaderMap, HeaderValue, StatusCode};
+use serde_json::json;
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+/// Serves `pages` of avatars (by hex public key), each request taking 10ms.
+/// Records the most requests it served at once.
+struct PagedTransport {
+    pages: Vec<Vec<&'static str>>,
+    /// Page reporting no next page, if not the last one.
+    end: Option<usize>,
+    /// `created_at` of every proof.
+    created_at: &'static str,
+    in_flight: AtomicUsize,
+    max_in_flight: AtomicUsize,
+    requested: Mutex<Vec<usize>>,
+}
+
+impl PagedTransport {
+    fn new(pages: Vec<Vec<&'static str>>) -> Self {
+        Self {
+            pages,
+            end: None,
+            created_at: "1648200542",
+            in_flight: AtomicUsize::new(0),
+            max_in_flight: AtomicUsize::new(0),
+            requested: Mutex::new(vec![]),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Transport for PagedTransport {
+    async fn send(&self, request: TransportRequest) -> Result<TransportResponse> {
+        let page: usize = request
+            .url
+            .query_pairs()
+            .find(|(name, _)| name == "page")
+            .map(|(_, page)| page.parse().unwrap())
+            .unwrap();
+        self.requested.lock().unwrap().push(page);
+        let in_flight = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+        self.max_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        self.in_flight.fetch_sub(1, Ordering::SeqCst);
+
+        let per = self.pages[0].len();
+        let ids: Vec<_> = self
+            .pages
+            .get(page - 1)
+            .cloned()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|avatar| {
+                json!({
+                    "avatar": avatar,
+                    "last_arweave_id": "",
+                    "proofs": [{
+                        "platform": "twitter",
+                        "identity": "yeiwb",
+                        "created_at": self.created_at,
+                        "last_checked_at": "1648200542",
+                        "is_valid": true,
+                        "invalid_reason": "",
+                    }],
+                })
+            })
+            .collect();
+        let next = if page < self.pages.len() && self.end != Some(page) {
+            page + 1
+        } else {
+            0
+        };
+        let body = json!({
+            "pagination": {
+                "total": self.pages.len() * per,
+                "per": per,
+                "current": page,
+                "next": next,
+            },
+            "ids": ids,
+        });
+        Ok(TransportResponse {
+            status: StatusCode::OK,
+            headers: HeaderMap::new(),
+            body: body.to_string().into_bytes(),
+        })
+    }
+}
+
+fn avatars_of(avatars: &[Avatar]) -> Vec<String> {
+    avatars
+        .iter()
+        .map(|avatar| hex_encode(&avatar.avatar))
+        .collect()
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_prefetch_in_order() -> Result<()> {
+    let transport = Arc::new(PagedTransport::new(vec![
+        vec!["0x01", "0x02"],
+        vec!["0x03", "0x04"],
+        vec!["0x05", "0x06"],
+        vec!["0x07", "0x08"],
+        vec!["0x09", "0x0a"],
+    ]));
+    let client = Endpoint::Staging.with_transport(transport.clone());
+    let avatars = client
+        .find_by_with(
+            Platform::Twitter,
+            "yeiwb",
+            QueryOptions::new().concurrency(2),
+        )
+        .await?;
+    assert_eq!(
+        vec!["01", "02", "03", "04", "05", "06", "07", "08", "09", "0a"],
+        avatars_of(&avatars)
+    );
+    assert_eq!(2, transport.max_in_flight.load(Ordering::SeqCst));
+    assert_eq!(vec![1, 2, 3, 4, 5], *transport.requested.lock().unwrap());
+
+    // Limited to pages 2 and 3.
+    let transport = Arc::new(PagedTransport::new(transport.pages.clone()));
+    let client = Endpoint::Staging.with_transport(transport.clone());
+    let options = QueryOptions::new()
+        .start_page(2)
+        .max_pages(2)
+        .concurrency(8);
+    let avatars = client
+        .find_by_with(Platform::Twitter, "yeiwb", options)
+        .await?;
+    assert_eq!(vec!["03", "04", "05", "06"], avatars_of(&avatars));
+    assert_eq!(vec![2, 3], *transport.requested.lock().unwrap());
+    Ok(())
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_malformed_records() {
+    let avatar = Secp256k1KeyPair::generate(&mut rand::rngs::OsRng);
+    let bad_hex =
+        Endpoint::Staging.with_transport(Arc::new(PagedTransport::new(vec![vec!["0x01", "0xzz"]])));
+    let out_of_range = Endpoint::Staging.with_transport(Arc::new(PagedTransport {
+        created_at: "9223372036854775807",
+        ..PagedTransport::new(vec![vec!["0x01"]])
+    }));
+    let not_a_number = Endpoint::Staging.with_transport(Arc::new(PagedTransport {
+        created_at: "yesterday",
+        ..PagedTransport::new(vec![vec!["0x01"]])
+    }));
+
+    for client in [&bad_hex, &not_a_number] {
+        let result = client.find_by(Platform::Twitter, "yeiwb", true).await;
+        assert!(matches!(result, Err(Error::ValidationError(_))));
+        let result = client.find_by_many(Platform::Twitter, &["yeiwb"]).await;
+        assert!(matches!(result, Err(Error::ValidationError(_))));
+        let result = client.find_by_avatar(&avatar).await;
+        assert!(matches!(result, Err(Error::ValidationError(_))));
+    }
+
+    let result = out_of_range.find_by(Platform::Twitter, "yeiwb", true).await;
+    assert!(matches!(result, Err(Error::TimestampOutOfRange(i64::MAX))));
+    let mut pages =
+        Box::pin(out_of_range.find_by_pages(Platform::Twitter, "yeiwb", QueryOptions::new()));
+    assert!(matches!(
+        pages.next().await,
+        Some(Err(Error::TimestampOutOfRange(i64::MAX)))
+    ));
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_prefetch_stops_at_early_end() -> Result<()> {
+    // Page 2 says it is the last one, though the total told 5 pages.
+    let transport = Arc::new(PagedTransport {
+        end: Some(2),
+        ..PagedTransport::new(vec![
+            vec!["0x01", "0x02"],
+            vec!["0x03", "0x04"],
+            vec!["0x05", "0x06"],
+            vec!["0x07", "0x08"],
+            vec!["0x09", "0x0a"],
+        ])
+    });
+    let client = Endpoint::Staging.with_transport(transport.clone());
+    let avatars = client
+        .find_by_with(
+            Platform::Twitter,
+            "yeiwb",
+            QueryOptions::new().concurrency(4),
+        )
+        .await?;
+    assert_eq!(vec!["01", "02", "03", "04"], avatars_of(&avatars));
+    Ok(())
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_dedup_shifted_avatars() -> Result<()> {
+    // `0x02` moved to the second page between both requests.
+    let transport = Arc::new(PagedTransport::new(vec![
+        vec!["0x01", "0x02"],
+        vec!["0x02", "0x03"],
+    ]));
+    let client = Endpoint::Staging.with_transport(transport);
+    let avatars = client.find_by(Platform::Twitter, "yeiwb", true).await?;
+    assert_eq!(vec!["01", "02", "03"], avatars_of(&avatars));
+    Ok(())
+}
+
+fn avatar(key: &str, proofs: &[(Platform, bool)]) -> Avatar {
+    Avatar {
+        avatar: key.as_bytes().to_vec(),
+        last_arweave_id: String::new(),
+        proofs: proofs
+            .iter()
+            .map(|(platform, is_valid)| Proof {
+                platform: *platform,
+                identity: "yeiwb".into(),
+                created_at: ts_to_naive(0, 0),
+                last_checked_at: ts_to_naive(0, 0),
+                is_valid: *is_valid,
+                invalid_reason: (!is_valid).then(|| "post deleted".into()),
+            })
+            .collect(),
+    }
+}
+
+#[test]
+fn test_client_side_filters() {
+    let avatars = vec![
+        avatar("a", &[(Platform::Twitter, true), (Platform::Github, false)]),
+        avatar("b", &[(Platform::Twitter, false)]),
+    ];
+    let keys = |avatars: Vec<Avatar>| -> Vec<Vec<u8>> {
+        avatars.into_iter().map(|avatar| avatar.avatar).collect()
+    };
+
+    let valid = QueryOptions::new()
+        .only_valid_proofs()
+        .filter(avatars.clone());
+    assert_eq!(vec![b"a".to_vec()], keys(valid.clone()));
+    assert_eq!(1, valid[0].proofs.len());
+
+    let with_github = QueryOptions::new().with_platform(Platform::Github);
+    assert_eq!(
+        vec![b"a".to_vec()],
+        keys(with_github.filter(avatars.clone()))
+    );
+    assert!(with_github.only_valid_proofs().filter(avatars).is_empty());
+}
+
+/// Server knowing `avatar` with an invalid twitter proof of `yeiwb` on `GET v1/proof`.
+/// With `has_route`, `GET v1/proof/exists` answers a JSON `404` for any binding;
+/// without it, that route is missing and answers the plain text `404` of Go's `net/http`.
+struct ExistsTransport {
+    has_route: bool,
+    avatar: String,
+    paths: Mutex<Vec<String>>,
+}
+
+#[async_trait::async_trait]
+impl Transport for ExistsTransport {
+    async fn send(&self, request: TransportRequest) -> Result<TransportResponse> {
+        let path = request.url.path().to_string();
+        self.paths.lock().unwrap().push(path.clone());
+        let (status, content_type, body) = match path.as_str() {
+            "/v1/proof/exists" if self.has_route => (
+                StatusCode::NOT_FOUND,
+                "application/json",
+                json!({ "message": "binding not found" }).to_string(),
+            ),
+            "/v1/proof" => (
+                StatusCode::OK,
+                "application/json",
+                json!({
+                    "pagination": { "total": 1, "per": 20, "current": 1, "next": 0 },
+                    "ids": [{
+                        "avatar": self.avatar,
+                        "last_arweave_id": "",
+                        "proofs": [{
+                            "platform": "twitter",
+                            "identity": "yeiwb",
+                            "created_at": "1648200542",
+                            "last_checked_at": "1648200542",
+                            "is_valid": false,
+                            "invalid_reason": "post deleted",
+                        }],
+                    }],
+                })
+                .to_string(),
+            ),
+            _ => (
+                StatusCode::NOT_FOUND,
+                "text/plain; charset=utf-8",
+                "404 page not found\n".to_string(),
+            ),
+        };
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+        Ok(TransportResponse {
+            status,
+            headers,
+            body: body.into_bytes(),
+        })
+    }
+}
+
+#[derive(Default)]
+struct RecordingMetrics(Mutex<Vec<RequestMetrics>>);
+
+impl MetricsRecorder for RecordingMetrics {
+    fn record(&self, metrics: &RequestMetrics) {
+        self.0.lock().unwrap().push(metrics.clone());
+    }
+}
+
+#[tokio::test]
+async fn test_exists_fallback() -> Result<()> {
+    let avatar = Secp256k1KeyPair::generate(&mut rand::rngs::OsRng);
+    let transport = |has_route| {
+        Arc::new(ExistsTransport {
+            has_route,
+            avatar: format!("0x{}", hex_encode(&avatar.pk.serialize())),
+            paths: Mutex::new(vec![]),
+        })
+    };
+
+    // No exists route: the avatar is looked up instead, and the route is not asked again.
+    let legacy = transport(false);
+    let recorder = Arc::new(RecordingMetrics::default());
+    let client = Endpoint::Staging
+        .with_transport(legacy.clone())
+        .with_metrics(recorder.clone());
+    assert_eq!(
+        Binding::Invalid {
+            reason: Some("post deleted".into())
+        },
+        client.exists(Platform::Twitter, "YeiWB", &avatar).await?
+    );
+    assert_eq!(
+        Binding::NotBound,
+        client.exists(Platform::Github, "yeiwb", &avatar).await?
+    );
+    assert_eq!(
+        vec!["/v1/proof/exists", "/v1/proof", "/v1/proof"],
+        *legacy.paths.lock().unwrap()
+    );
+
+    // A JSON 404 is the answer itself.
+    let current = transport(true);
+    let client = Endpoint::Staging
+        .with_transport(current.clone())
+        .with_metrics(recorder.clone());
+    assert_eq!(
+        Binding::NotBound,
+        client.exists(Platform::Twitter, "yeiwb", &avatar).await?
+    );
+    assert_eq!(vec!["/v1/proof/exists"], *current.paths.lock().unwrap());
+
+    // Neither 404 is reported as a failed call.
+    let recorded = recorder.0.lock().unwrap();
+    assert_eq!(4, recorded.len());
+    assert!(recorded.iter().all(|metrics| metrics.error.is_none()));
+    Ok(())
+}
+
+#[test]
+fn test_batches() {
+    let identities =
+        |names: &[&str]| -> Vec<String> { names.iter().map(|name| name.to_string()).collect() };
+    let options = BatchOptions {
+        max_identities: 2,
+        ..Default::default()
+    };
+    assert_eq!(
+        vec![identities(&["a", "b"]), identities(&["c"])],
+        batches(identities(&["a", "b", "c"]), &options)
+    );
+
+    // `é` takes 6 bytes once encoded, and each separator 3.
+    let options = BatchOptions {
+        max_param_length: 12,
+        ..Default::default()
+    };
+    assert_eq!(
+        vec![identities(&["é", "ab"]), identities(&["é"])],
+        batches(identities(&["é", "ab", "é"]), &options)
+    );
+}
+
+/// Serves empty pages of a single avatar, jumping from page 1 to page `jump_to`,
+/// then pointing back to page 1.
+struct JumpingTransport {
+    jump_to: usize,
+    requested: Mutex<Vec<usize>>,
+}
+
+#[async_trait::async_trait]
+impl Transport for JumpingTransport {
+    async fn send(&self, request: TransportRequest) -> Result<TransportResponse> {
+        let page: usize = request
+            .url
+            .query_pairs()
+            .find(|(name, _)| name == "page")
+            .map(|(_, page)| page.parse().unwrap())
+            .unwrap();
+        self.requested.lock().unwrap().push(page);
+        let next = if page == 1 { self.jump_to } else { 1 };
+        let body = json!({
+            "pagination": { "total": 100, "per": 1, "current": page, "next": next },
+            "ids": [],
+        });
+        Ok(TransportResponse {
+            status: StatusCode::OK,
+            headers: HeaderMap::new(),
+            body: body.to_string().into_bytes(),
+        })
+    }
+}
+
+#[tokio::test]
+async fn test_find_by_many_follows_next() -> Result<()> {
+    let transport = Arc::new(JumpingTransport {
+        jump_to: 3,
+        requested: Mutex::new(vec![]),
+    });
+    let client = Endpoint::Staging.with_transport(transport.clone());
+    client.find_by_many(Platform::Twitter, &["yeiwb"]).await?;
+    // Page 2 is skipped as told, and the loop back to page 1 is not followed.
+    assert_eq!(vec![1, 3], *transport.requested.lock().unwrap());
+    Ok(())
+}
@@ -1,9 +1,16 @@
 pub(crate) mod raw;
 
-use self::raw::query::{AvatarWithProof, SingleProof};
-use crate::util::{hex_decode, ts_string_to_naive};
+use self::raw::{
+    exists,
+    query::{AvatarWithProof, SingleProof},
+};
+use crate::{
+    types::{Error, Result},
+    util::{hex_decode, ts_string_to_naive},
+};
 use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use strum_macros::{Display, EnumString};
 
 /// All actios available when modifying ProofChain.
@@ -18,7 +25,7 @@ pub enum Action {
 }
 
 /// All platforms supported by ProofService
-#[derive(Serialize, Deserialize, Clone, Copy, Display, EnumString, Eq, PartialEq)]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Display, EnumString, Eq, PartialEq)]
 pub enum Platform {
     #[serde(rename = "github")]
     #[strum(serialize = "github")]
@@ -63,13 +70,22 @@ pub struct Avatar {
     pub proofs: Vec<Proof>,
 }
 
-impl From<AvatarWithProof> for Avatar {
-    fn from(raw_avatar: AvatarWithProof) -> Self {
-        Self {
-            avatar: hex_decode(&raw_avatar.avatar).expect("Error when decoding avatar hexstring"),
+impl TryFrom<AvatarWithProof> for Avatar {
+    type Error = Error;
+
+    fn try_from(raw_avatar: AvatarWithProof) -> Result<Self> {
+        let avatar = hex_decode(&raw_avatar.avatar).map_err(|err| {
+            Error::ValidationError(format!("avatar {:?}: {}", raw_avatar.avatar, err))
+        })?;
+        Ok(Self {
+            avatar,
             last_arweave_id: raw_avatar.last_arweave_id,
-            proofs: raw_avatar.proofs.into_iter().map(|p| p.into()).collect(),
-        }
+            proofs: raw_avatar
+                .proofs
+                .into_iter()
+                .map(Proof::try_from)
+                .collect::<Result<_>>()?,
+        })
     }
 }
 
@@ -90,21 +106,110 @@ pub struct Proof {
     pub invalid_reason: Option<String>,
 }
 
-impl From<SingleProof> for Proof {
-    fn from(raw_proof: SingleProof) -> Self {
-        Self {
+impl TryFrom<SingleProof> for Proof {
+    type Error = Error;
+
+    fn try_from(raw_proof: SingleProof) -> Result<Self> {
+        Ok(Self {
             platform: raw_proof.platform,
             identity: raw_proof.identity,
-            created_at: ts_string_to_naive(&raw_proof.created_at)
-                .expect("Error when parsing created_at"),
-            last_checked_at: ts_string_to_naive(&raw_proof.created_at)
-                .expect("Error when parsing last_checked_at"),
+            created_at: timestamp("created_at", &raw_proof.created_at)?,
+            last_checked_at: timestamp("last_checked_at", &raw_proof.last_checked_at)?,
             is_valid: raw_proof.is_valid,
-            invalid_reason: if raw_proof.invalid_reason.len() == 0 {
+            invalid_reason: if raw_proof.invalid_reason.is_empty() {
                 None
             } else {
                 Some(raw_proof.invalid_reason.clone())
             },
+        })
+    }
+}
+
+/// Parse the timestamp `field` of a proof.
+fn timestamp(field: &str, value: &str) -> Result<NaiveDateTime> {
+    ts_string_to_naive(value).map_err(|err| match err {
+        Error::ParseIntError(err) => {
+            Error::ValidationError(format!("{} {:?}: {}", field, value, err))
+        }
+        err => err,
+    })
+}
+
+/// Whether a `platform` / `identity` pair is bound to an avatar.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Binding {
+    /// Bound, and the proof passed its last check.
+    Valid,
+    /// Bound, but the proof failed its last check.
+    Invalid {
+        /// Why the check failed, if told by the server.
+        reason: Option<String>,
+    },
+    /// No proof of this pair under the avatar.
+    NotBound,
+}
+
+impl Binding {
+    /// [Binding::Valid] or [Binding::Invalid].
+    pub fn is_bound(&self) -> bool {
+        !matches!(self, Binding::NotBound)
+    }
+
+    /// [Binding::Valid] only.
+    pub fn is_valid(&self) -> bool {
+        matches!(self, Binding::Valid)
+    }
+}
+
+impl From<&Proof> for Binding {
+    fn from(proof: &Proof) -> Self {
+        if proof.is_valid {
+            Binding::Valid
+        } else {
+            Binding::Invalid {
+                reason: proof.invalid_reason.clone(),
+            }
         }
     }
 }
+
+impl From<exists::Response> for Binding {
+    fn from(raw: exists::Response) -> Self {
+        if raw.is_valid {
+            Binding::Valid
+        } else {
+            Binding::Invalid {
+                reason: (!raw.invalid_reason.is_empty()).then_some(raw.invalid_reason),
+            }
+        }
+    }
+}
+
+/// Status reported by `GET /healthz` of a ProofService server.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Health {
+    /// Greeting of the server, e.g. `proof service`.
+    pub hello: String,
+    /// Platforms the server accepts proofs of. Names unknown to [Platform] are kept as-is.
+    #[serde(default)]
+    pub platforms: Vec<String>,
+    /// Deployment environment, e.g. `production`, if exposed.
+    #[serde(default)]
+    pub environment: Option<String>,
+    /// Server build version, if exposed.
+    #[serde(default)]
+    pub version: Option<String>,
+    /// Any other field reported by the server.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl Health {
+    /// [Health::platforms] known to this SDK.
+    pub fn supported_platforms(&self) -> Vec<Platform> {
+        self.platforms
+            .iter()
+            .filter_map(|platform| platform.parse().ok())
+            .collect()
+    }
+}
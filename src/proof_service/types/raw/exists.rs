@@ -0,0 +1,8 @@
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct Response {
+    pub is_valid: bool,
+    #[serde(default)]
+    pub invalid_reason: String,
+}
@@ -1,3 +1,4 @@
+pub(crate) mod exists;
 pub(crate) mod payload;
 pub(crate) mod query;
 pub(crate) mod upload;
@@ -15,6 +15,36 @@ pub struct Request {
     pub platform: Platform,
     pub identity: Vec<String>,
     pub page: Option<usize>,
+    pub exact: Option<bool>,
+    pub sort: Option<String>,
+    pub order: Option<String>,
+    pub size: Option<usize>,
+}
+
+impl Request {
+    /// Query parameters of `GET v1/proof`. Identities are comma-separated.
+    pub fn query_pairs(&self) -> Vec<(&'static str, String)> {
+        let mut pairs = vec![
+            ("platform", self.platform.to_string()),
+            ("identity", self.identity.join(",")),
+        ];
+        if let Some(page) = self.page {
+            pairs.push(("page", page.to_string()));
+        }
+        if let Some(exact) = self.exact {
+            pairs.push(("exact", exact.to_string()));
+        }
+        if let Some(sort) = &self.sort {
+            pairs.push(("sort", sort.clone()));
+        }
+        if let Some(order) = &self.order {
+            pairs.push(("order", order.clone()));
+        }
+        if let Some(size) = self.size {
+            pairs.push(("size", size.to_string()));
+        }
+        pairs
+    }
 }
 
 #[derive(Deserialize)]
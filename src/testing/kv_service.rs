@@ -0,0 +1,181 @@
+use super::{bad_request, new_uuid, normalize_avatar, verify_signature, Reply, State};
+use crate::{
+    proof_service::Platform,
+    util::{
+        base64_decode,
+        http::{BINDING_NOT_FOUND, PAYLOAD_EXPIRED, SIGNATURE_INVALID},
+    },
+};
+use http::StatusCode;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+#[derive(Deserialize)]
+pub(super) struct PayloadRequest {
+    avatar: String,
+    platform: Platform,
+    identity: String,
+    patch: Value,
+}
+
+#[derive(Deserialize)]
+pub(super) struct UploadRequest {
+    avatar: String,
+    platform: Platform,
+    identity: String,
+    signature: String,
+    uuid: String,
+    created_at: i64,
+    patch: Value,
+}
+
+/// Payload issued by `POST v1/kv/payload`.
+pub(super) struct Payload {
+    avatar: String,
+    platform: Platform,
+    identity: String,
+    patch: Value,
+    created_at: i64,
+    sign_payload: String,
+}
+
+/// `GET v1/kv?avatar=`
+pub(super) fn query(state: &State, query: &HashMap<String, String>) -> Reply {
+    let avatar = normalize_avatar(
+        query
+            .get("avatar")
+            .ok_or_else(|| bad_request("avatar is required"))?,
+    )?;
+    Ok((StatusCode::OK, avatar_content(state, &avatar)))
+}
+
+/// `GET v1/kv/by_identity?platform=&identity=`
+pub(super) fn query_by_identity(state: &State, query: &HashMap<String, String>) -> Reply {
+    let (Some(platform), Some(identity)) = (query.get("platform"), query.get("identity")) else {
+        return Err(bad_request("platform and identity are required"));
+    };
+    let identity = identity.to_lowercase();
+    let values: Vec<_> = state
+        .kv
+        .iter()
+        .filter(|((_, kv_platform, kv_identity), _)| {
+            kv_platform == platform && *kv_identity == identity
+        })
+        .map(|((avatar, _, _), content)| json!({ "avatar": avatar, "content": content }))
+        .collect();
+    Ok((StatusCode::OK, json!({ "values": values })))
+}
+
+/// `POST v1/kv/payload`
+pub(super) fn payload(state: &mut State, request: PayloadRequest) -> Reply {
+    let avatar = normalize_avatar(&request.avatar)?;
+    let identity = request.identity.to_lowercase();
+    let bound = if request.platform == Platform::NextID {
+        normalize_avatar(&identity)? == avatar
+    } else {
+        state
+            .avatars
+            .iter()
+            .any(|record| record.avatar == avatar && record.has_proof(request.platform, &identity))
+    };
+    if !bound {
+        return Err((StatusCode::NOT_FOUND, BINDING_NOT_FOUND.to_string()));
+    }
+
+    let uuid = new_uuid();
+    let created_at = chrono::Utc::now().timestamp();
+    let sign_payload = json!({
+        "action": "kv",
+        "created_at": created_at,
+        "patch": request.patch,
+        "prev": null,
+        "uuid": uuid,
+    })
+    .to_string();
+    state.kv_payloads.insert(
+        uuid.clone(),
+        Payload {
+            avatar,
+            platform: request.platform,
+            identity,
+            patch: request.patch,
+            created_at,
+            sign_payload: sign_payload.clone(),
+        },
+    );
+
+    Ok((
+        StatusCode::OK,
+        json!({
+            "uuid": uuid,
+            "sign_payload": sign_payload,
+            "created_at": created_at,
+        }),
+    ))
+}
+
+/// `POST v1/kv`
+pub(super) fn upload(state: &mut State, request: UploadRequest) -> Reply {
+    let avatar = normalize_avatar(&request.avatar)?;
+    let identity = request.identity.to_lowercase();
+    let payload = state
+        .kv_payloads
+        .get(&request.uuid)
+        .filter(|payload| {
+            payload.avatar == avatar
+                && payload.platform == request.platform
+                && payload.identity == identity
+                && payload.patch == request.patch
+                && payload.created_at == request.created_at
+        })
+        .ok_or_else(|| bad_request(PAYLOAD_EXPIRED))?;
+    let signature =
+        base64_decode(&request.signature).map_err(|_| bad_request(SIGNATURE_INVALID))?;
+    verify_signature(&signature, &payload.sign_payload, &payload.avatar)?;
+
+    let payload = state.kv_payloads.remove(&request.uuid).unwrap();
+    let content = state
+        .kv
+        .entry((
+            avatar.clone(),
+            payload.platform.to_string(),
+            payload.identity,
+        ))
+        .or_insert_with(|| json!({}));
+    merge_patch(content, &payload.patch);
+
+    Ok((StatusCode::OK, avatar_content(state, &avatar)))
+}
+
+/// All KV of `avatar`, in the shape of `GET v1/kv`.
+fn avatar_content(state: &State, avatar: &str) -> Value {
+    let proofs: Vec<_> = state
+        .kv
+        .iter()
+        .filter(|((kv_avatar, _, _), _)| kv_avatar == avatar)
+        .map(|((_, platform, identity), content)| {
+            json!({ "platform": platform, "identity": identity, "content": content })
+        })
+        .collect();
+    json!({ "avatar": avatar, "proofs": proofs })
+}
+
+/// Apply a JSON merge patch (RFC 7396): objects are merged recursively, `null` removes a key.
+fn merge_patch(target: &mut Value, patch: &Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = json!({});
+    }
+    let target = target.as_object_mut().unwrap();
+    for (key, value) in patch {
+        if value.is_null() {
+            target.remove(key);
+        } else {
+            merge_patch(target.entry(key.clone()).or_insert(Value::Null), value);
+        }
+    }
+}
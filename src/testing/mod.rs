@@ -0,0 +1,289 @@
+mod kv_service;
+mod proof_service;
+#[cfg(test)]
+mod tests;
+
+use crate::{
+    kv_service as kv, proof_service as ps,
+    types::Result,
+    util::{
+        crypto::Secp256k1KeyPair,
+        hex_encode,
+        http::{SIGNATURE_INVALID, SIGNATURE_MISMATCH},
+    },
+};
+use http::{header::CONTENT_TYPE, Method, Request, Response, StatusCode};
+use hyper::{
+    service::{make_service_fn, service_fn},
+    Body, Server,
+};
+use serde_json::{json, Value};
+use std::{
+    collections::{BTreeMap, HashMap},
+    convert::Infallible,
+    net::TcpListener,
+    sync::{Arc, Mutex},
+};
+use tokio::sync::oneshot;
+
+/// Results per page of `GET v1/proof` unless set by [MockServer::start_with_page_size].
+pub const DEFAULT_PAGE_SIZE: usize = 20;
+
+/// Result of a route handler: JSON body on success, error message otherwise.
+type Reply = std::result::Result<(StatusCode, Value), (StatusCode, String)>;
+
+fn bad_request(message: impl Into<String>) -> (StatusCode, String) {
+    (StatusCode::BAD_REQUEST, message.into())
+}
+
+/// In-memory state shared by ProofService and KVService routes.
+struct State {
+    page_size: usize,
+    /// Posts published by [MockServer::publish_post], by proof location.
+    posts: HashMap<String, String>,
+    /// Issued and not yet used ProofService payloads, by UUID.
+    proof_payloads: HashMap<String, proof_service::Payload>,
+    /// Avatars in binding order.
+    avatars: Vec<proof_service::AvatarRecord>,
+    /// Issued and not yet used KVService payloads, by UUID.
+    kv_payloads: HashMap<String, kv_service::Payload>,
+    /// KV content by `(avatar, platform, identity)`.
+    kv: BTreeMap<(String, String, String), Value>,
+}
+
+/// In-process ProofService and KVService server with in-memory state, for integration tests.
+///
+/// Serves the routes this SDK uses (`healthz`, `v1/proof`, `v1/proof/payload`, `v1/proof/exists`,
+/// `v1/kv`, `v1/kv/payload` and `v1/kv/by_identity`) on a random local port:
+/// - payloads are issued like the real servers do, and signatures are checked against them;
+/// - proof posts are not fetched from any platform: publish them with [MockServer::publish_post];
+/// - proofs are never re-checked, so they are always reported valid;
+/// - KV patches follow JSON merge patch semantics and need the platform / identity to be bound first.
+///
+/// The server stops when dropped.
+///
+/// # Examples
+/// ```rust
+/// # #[tokio::main]
+/// # async fn main() {
+/// # use nextid_sdk::testing::MockServer;
+/// # use nextid_sdk::proof_service::Platform;
+/// let server = MockServer::start().await.unwrap();
+/// let avatars = server.proof_service().find_by(Platform::Twitter, "yeiwb", false).await.unwrap();
+/// assert!(avatars.is_empty());
+/// # }
+/// ```
+pub struct MockServer {
+    url: String,
+    state: Arc<Mutex<State>>,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl MockServer {
+    /// Start a server on `127.0.0.1` in the current tokio runtime.
+    pub async fn start() -> Result<Self> {
+        Self::start_with_page_size(DEFAULT_PAGE_SIZE).await
+    }
+
+    /// Same as [MockServer::start], with `page_size` results per page of `GET v1/proof`.
+    pub async fn start_with_page_size(page_size: usize) -> Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        listener.set_nonblocking(true)?;
+        let url = format!("http://{}", listener.local_addr()?);
+        let state = Arc::new(Mutex::new(State {
+            page_size: page_size.max(1),
+            posts: HashMap::new(),
+            proof_payloads: HashMap::new(),
+            avatars: vec![],
+            kv_payloads: HashMap::new(),
+            kv: BTreeMap::new(),
+        }));
+
+        let service_state = state.clone();
+        let make_service = make_service_fn(move |_| {
+            let state = service_state.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    let state = state.clone();
+                    async move { Ok::<_, Infallible>(handle(&state, request).await) }
+                }))
+            }
+        });
+        let (shutdown, stopped) = oneshot::channel::<()>();
+        let server = Server::from_tcp(listener)?
+            .serve(make_service)
+            .with_graceful_shutdown(async {
+                let _ = stopped.await;
+            });
+        tokio::spawn(server);
+
+        Ok(Self {
+            url,
+            state,
+            shutdown: Some(shutdown),
+        })
+    }
+
+    /// Root URL of this server, e.g. `http://127.0.0.1:34567`.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// ProofService endpoint pointing to this server.
+    pub fn proof_endpoint(&self) -> ps::Endpoint {
+        ps::Endpoint::Custom(self.url.clone())
+    }
+
+    /// KVService endpoint pointing to this server.
+    pub fn kv_endpoint(&self) -> kv::Endpoint {
+        kv::Endpoint::Custom(self.url.clone())
+    }
+
+    /// ProofService client pointing to this server.
+    pub fn proof_service(&self) -> ps::Client {
+        self.proof_endpoint().into()
+    }
+
+    /// KVService client pointing to this server.
+    pub fn kv_service(&self) -> kv::Client {
+        self.kv_endpoint().into()
+    }
+
+    /// Make `content` reachable at `proof_location`, as if it was posted on the platform.
+    /// Fill the `post_content` of a payload with the signature, then publish it before submitting.
+    pub fn publish_post(&self, proof_location: &str, content: &str) {
+        self.state
+            .lock()
+            .unwrap()
+            .posts
+            .insert(proof_location.to_string(), content.to_string());
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
+
+async fn handle(state: &Mutex<State>, request: Request<Body>) -> Response<Body> {
+    let (parts, body) = request.into_parts();
+    let body = match hyper::body::to_bytes(body).await {
+        Ok(body) => body,
+        Err(err) => return json_response(Err(bad_request(err.to_string()))),
+    };
+    let query: HashMap<String, String> = parts
+        .uri
+        .query()
+        .map(|query| {
+            url::form_urlencoded::parse(query.as_bytes())
+                .into_owned()
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let mut state = state.lock().unwrap();
+    let reply = match (&parts.method, parts.uri.path()) {
+        (&Method::GET, "/healthz") => Ok(healthz()),
+        (&Method::GET, "/v1/proof") => proof_service::query(&state, &query),
+        (&Method::GET, "/v1/proof/exists") => proof_service::exists(&state, &query),
+        (&Method::POST, "/v1/proof/payload") => {
+            parse(&body).and_then(|request| proof_service::payload(&mut state, request))
+        }
+        (&Method::POST, "/v1/proof") => {
+            parse(&body).and_then(|request| proof_service::upload(&mut state, request))
+        }
+        (&Method::GET, "/v1/kv") => kv_service::query(&state, &query),
+        (&Method::GET, "/v1/kv/by_identity") => kv_service::query_by_identity(&state, &query),
+        (&Method::POST, "/v1/kv/payload") => {
+            parse(&body).and_then(|request| kv_service::payload(&mut state, request))
+        }
+        (&Method::POST, "/v1/kv") => {
+            parse(&body).and_then(|request| kv_service::upload(&mut state, request))
+        }
+        // Unknown routes get the plain text 404 of Go's `net/http`, like the real servers.
+        _ => {
+            return Response::builder()
+                .status(StatusCode::NOT_FOUND)
+                .header(CONTENT_TYPE, "text/plain; charset=utf-8")
+                .body(Body::from("404 page not found\n"))
+                .unwrap()
+        }
+    };
+    json_response(reply)
+}
+
+/// `GET /healthz`, answering for both services.
+fn healthz() -> (StatusCode, Value) {
+    (
+        StatusCode::OK,
+        json!({
+            "hello": "nextid mock server",
+            "platforms": [
+                "github", "nextid", "twitter", "keybase", "ethereum",
+                "discord", "dotbit", "solana", "dns", "minds",
+            ],
+            "environment": "mock",
+            "version": env!("CARGO_PKG_VERSION"),
+        }),
+    )
+}
+
+fn parse<T: serde::de::DeserializeOwned>(
+    body: &[u8],
+) -> std::result::Result<T, (StatusCode, String)> {
+    serde_json::from_slice(body).map_err(|err| bad_request(format!("invalid request: {}", err)))
+}
+
+fn json_response(reply: Reply) -> Response<Body> {
+    let (status, body) = match reply {
+        Ok(reply) => reply,
+        Err((status, message)) => (status, json!({ "message": message })),
+    };
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json; charset=utf-8")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+/// `0x`-prefixed compressed public key, the form both servers answer with.
+fn normalize_avatar(public_key: &str) -> std::result::Result<String, (StatusCode, String)> {
+    let avatar = Secp256k1KeyPair::from_pk_hex(public_key)
+        .map_err(|_| bad_request(format!("invalid public key: {}", public_key)))?;
+    Ok(format!(
+        "0x{}",
+        hex_encode(&avatar.pk.serialize_compressed())
+    ))
+}
+
+/// Check `signature` is a personal signature of `sign_payload` made by `avatar` (normalized).
+fn verify_signature(
+    signature: &[u8],
+    sign_payload: &str,
+    avatar: &str,
+) -> std::result::Result<(), (StatusCode, String)> {
+    let recovered =
+        Secp256k1KeyPair::recover_from_personal_signature(&signature.to_vec(), sign_payload)
+            .map_err(|_| bad_request(SIGNATURE_INVALID))?;
+    if format!("0x{}", hex_encode(&recovered.pk.serialize_compressed())) != avatar {
+        return Err(bad_request(SIGNATURE_MISMATCH));
+    }
+    Ok(())
+}
+
+/// Random UUID (v4 layout).
+fn new_uuid() -> String {
+    let bytes: [u8; 16] = rand::random();
+    let hex = hex_encode(&bytes);
+    format!(
+        "{}-{}-4{}-a{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[13..16],
+        &hex[17..20],
+        &hex[20..32]
+    )
+}
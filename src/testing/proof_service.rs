@@ -0,0 +1,396 @@
+use super::{bad_request, new_uuid, normalize_avatar, verify_signature, Reply, State};
+use crate::{
+    proof_service::{Action, Platform},
+    util::{
+        base64_decode,
+        crypto::Secp256k1KeyPair,
+        eth_address_from_public_key, hex_decode,
+        http::{
+            BINDING_EXISTS, BINDING_NOT_FOUND, PAYLOAD_EXPIRED, PROOF_POST_NOT_FOUND,
+            SIGNATURE_INVALID, WALLET_SIGNATURE_MISMATCH,
+        },
+    },
+};
+use http::StatusCode;
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashMap;
+
+#[derive(Deserialize)]
+pub(super) struct PayloadRequest {
+    action: Action,
+    platform: Platform,
+    identity: String,
+    public_key: String,
+}
+
+#[derive(Deserialize)]
+pub(super) struct UploadRequest {
+    action: Action,
+    platform: Platform,
+    identity: String,
+    proof_location: String,
+    public_key: String,
+    uuid: String,
+    created_at: String,
+    #[serde(default)]
+    extra: Option<UploadExtra>,
+}
+
+#[derive(Deserialize, Default)]
+struct UploadExtra {
+    signature: Option<String>,
+    wallet_signature: Option<String>,
+}
+
+/// Payload issued by `POST v1/proof/payload`.
+pub(super) struct Payload {
+    action: Action,
+    platform: Platform,
+    identity: String,
+    avatar: String,
+    created_at: String,
+    sign_payload: String,
+}
+
+pub(super) struct AvatarRecord {
+    pub(super) avatar: String,
+    pub(super) proofs: Vec<ProofRecord>,
+}
+
+pub(super) struct ProofRecord {
+    pub(super) platform: Platform,
+    pub(super) identity: String,
+    created_at: i64,
+}
+
+impl AvatarRecord {
+    pub(super) fn has_proof(&self, platform: Platform, identity: &str) -> bool {
+        self.proofs
+            .iter()
+            .any(|proof| proof.platform == platform && proof.identity == identity)
+    }
+
+    /// Has a proof on `platform` whose identity contains `identity`.
+    fn has_similar_proof(&self, platform: Platform, identity: &str) -> bool {
+        self.proofs
+            .iter()
+            .any(|proof| proof.platform == platform && proof.identity.contains(identity))
+    }
+
+    /// When the first proof of this avatar was created.
+    fn activated_at(&self) -> i64 {
+        self.proofs
+            .iter()
+            .map(|proof| proof.created_at)
+            .min()
+            .unwrap_or_default()
+    }
+}
+
+/// Optional query parameter `name`, parsed.
+fn param<T: std::str::FromStr>(
+    query: &HashMap<String, String>,
+    name: &str,
+) -> std::result::Result<Option<T>, (StatusCode, String)> {
+    query
+        .get(name)
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|_| bad_request(format!("invalid {}: {}", name, value)))
+        })
+        .transpose()
+}
+
+/// `GET v1/proof?platform=&identity=[&page=&exact=&sort=&order=&size=]`.
+/// `identity` may hold several comma-separated values, matched exactly unless `exact=false`.
+pub(super) fn query(state: &State, query: &HashMap<String, String>) -> Reply {
+    let (Some(platform), Some(identity)) = (query.get("platform"), query.get("identity")) else {
+        return Err(bad_request("platform and identity are required"));
+    };
+    let platform: Platform = platform
+        .parse()
+        .map_err(|_| bad_request(format!("unknown platform: {}", platform)))?;
+    let page: usize = param(query, "page")?.unwrap_or(1).max(1);
+    let exact: bool = param(query, "exact")?.unwrap_or(true);
+    let per: usize = param(query, "size")?
+        .unwrap_or(state.page_size)
+        .clamp(1, 100);
+
+    let identities: Vec<String> = identity
+        .split(',')
+        .map(|identity| identity.trim().to_lowercase())
+        .collect();
+    let mut matched: Vec<&AvatarRecord> = if platform == Platform::NextID {
+        let avatars: Vec<String> = identities
+            .iter()
+            .filter_map(|identity| normalize_avatar(identity).ok())
+            .collect();
+        state
+            .avatars
+            .iter()
+            .filter(|record| avatars.contains(&record.avatar))
+            .collect()
+    } else {
+        state
+            .avatars
+            .iter()
+            .filter(|record| {
+                identities.iter().any(|identity| {
+                    if exact {
+                        record.has_proof(platform, identity)
+                    } else {
+                        record.has_similar_proof(platform, identity)
+                    }
+                })
+            })
+            .collect()
+    };
+    match query.get("sort").map(String::as_str) {
+        None | Some("activated_at") | Some("created_at") => {
+            matched.sort_by_key(|record| record.activated_at())
+        }
+        Some(sort) => return Err(bad_request(format!("unsupported sort key: {}", sort))),
+    }
+    match query.get("order").map(String::as_str) {
+        None | Some("asc") => {}
+        Some("desc") => matched.reverse(),
+        Some(order) => return Err(bad_request(format!("invalid order: {}", order))),
+    }
+
+    let total = matched.len();
+    let ids: Vec<_> = matched
+        .iter()
+        .skip((page - 1) * per)
+        .take(per)
+        .map(|record| {
+            let proofs: Vec<_> = record
+                .proofs
+                .iter()
+                .map(|proof| {
+                    json!({
+                        "platform": proof.platform,
+                        "identity": proof.identity,
+                        "created_at": proof.created_at.to_string(),
+                        "last_checked_at": proof.created_at.to_string(),
+                        "is_valid": true,
+                        "invalid_reason": "",
+                    })
+                })
+                .collect();
+            json!({
+                "avatar": record.avatar,
+                "last_arweave_id": "",
+                "proofs": proofs,
+            })
+        })
+        .collect();
+    let next = if page * per < total { page + 1 } else { 0 };
+    Ok((
+        StatusCode::OK,
+        json!({
+            "pagination": { "total": total, "per": per, "current": page, "next": next },
+            "ids": ids,
+        }),
+    ))
+}
+
+/// `GET v1/proof/exists`
+pub(super) fn exists(state: &State, query: &HashMap<String, String>) -> Reply {
+    let (Some(platform), Some(identity), Some(public_key)) = (
+        query.get("platform"),
+        query.get("identity"),
+        query.get("public_key"),
+    ) else {
+        return Err(bad_request(
+            "platform, identity and public_key are required",
+        ));
+    };
+    let platform: Platform = platform
+        .parse()
+        .map_err(|_| bad_request(format!("unknown platform: {}", platform)))?;
+    let avatar = normalize_avatar(public_key)?;
+    let identity = identity.to_lowercase();
+    let proof = state
+        .avatars
+        .iter()
+        .filter(|record| record.avatar == avatar)
+        .flat_map(|record| &record.proofs)
+        .find(|proof| proof.platform == platform && proof.identity == identity)
+        .ok_or((StatusCode::NOT_FOUND, BINDING_NOT_FOUND.to_string()))?;
+    Ok((
+        StatusCode::OK,
+        json!({
+            "created_at": proof.created_at.to_string(),
+            "last_checked_at": proof.created_at.to_string(),
+            "is_valid": true,
+            "invalid_reason": "",
+        }),
+    ))
+}
+
+/// `POST v1/proof/payload`
+pub(super) fn payload(state: &mut State, request: PayloadRequest) -> Reply {
+    let avatar = normalize_avatar(&request.public_key)?;
+    let identity = request.identity.to_lowercase();
+    let bound = state
+        .avatars
+        .iter()
+        .any(|record| record.avatar == avatar && record.has_proof(request.platform, &identity));
+    match request.action {
+        Action::Create if bound => return Err(bad_request(BINDING_EXISTS)),
+        Action::Delete if !bound => {
+            return Err((StatusCode::NOT_FOUND, BINDING_NOT_FOUND.to_string()))
+        }
+        _ => {}
+    }
+
+    let uuid = new_uuid();
+    let created_at = chrono::Utc::now().timestamp().to_string();
+    let sign_payload = json!({
+        "action": request.action,
+        "created_at": created_at,
+        "identity": identity,
+        "platform": request.platform,
+        "prev": null,
+        "uuid": uuid,
+    })
+    .to_string();
+    let post = format!(
+        "🎭 Verifying my {} ID @{} for @NextDotID.\nSig: %SIG_BASE64%\n\nNext.ID YOUR DIGITAL IDENTITIES IN ONE PLACE\n",
+        request.platform, identity
+    );
+    state.proof_payloads.insert(
+        uuid.clone(),
+        Payload {
+            action: request.action,
+            platform: request.platform,
+            identity,
+            avatar,
+            created_at: created_at.clone(),
+            sign_payload: sign_payload.clone(),
+        },
+    );
+
+    Ok((
+        StatusCode::OK,
+        json!({
+            "post_content": { "default": post },
+            "sign_payload": sign_payload,
+            "uuid": uuid,
+            "created_at": created_at,
+        }),
+    ))
+}
+
+/// `POST v1/proof`
+pub(super) fn upload(state: &mut State, request: UploadRequest) -> Reply {
+    let avatar = normalize_avatar(&request.public_key)?;
+    let identity = request.identity.to_lowercase();
+    let payload = state
+        .proof_payloads
+        .get(&request.uuid)
+        .filter(|payload| {
+            payload.action == request.action
+                && payload.platform == request.platform
+                && payload.identity == identity
+                && payload.avatar == avatar
+                && payload.created_at == request.created_at
+        })
+        .ok_or_else(|| bad_request(PAYLOAD_EXPIRED))?;
+
+    let extra = request.extra.unwrap_or_default();
+    if request.platform == Platform::Ethereum {
+        let avatar_valid = extra
+            .signature
+            .as_deref()
+            .map(|signature| verify_base64_signature(signature, payload))
+            .unwrap_or_else(|| Err(bad_request("avatar signature required")));
+        let wallet_valid = extra
+            .wallet_signature
+            .as_deref()
+            .map(|signature| verify_wallet_signature(signature, payload))
+            .unwrap_or_else(|| Err(bad_request("wallet signature required")));
+        match request.action {
+            Action::Create => avatar_valid.and(wallet_valid)?,
+            Action::Delete => avatar_valid.or(wallet_valid)?,
+        }
+    } else if let Some(signature) = extra.signature.as_deref() {
+        verify_base64_signature(signature, payload)?;
+    } else {
+        let post = state
+            .posts
+            .get(&request.proof_location)
+            .ok_or_else(|| bad_request(PROOF_POST_NOT_FOUND))?;
+        let signature = post
+            .lines()
+            .find_map(|line| line.trim().strip_prefix("Sig: "))
+            .ok_or_else(|| {
+                bad_request(format!("{}: no signature in proof post", SIGNATURE_INVALID))
+            })?;
+        verify_base64_signature(signature.trim(), payload)?;
+    }
+
+    let payload = state.proof_payloads.remove(&request.uuid).unwrap();
+    match payload.action {
+        Action::Create => {
+            let proof = ProofRecord {
+                platform: payload.platform,
+                identity: payload.identity,
+                created_at: payload.created_at.parse().unwrap_or_default(),
+            };
+            match state
+                .avatars
+                .iter_mut()
+                .find(|record| record.avatar == payload.avatar)
+            {
+                Some(record) if record.has_proof(proof.platform, &proof.identity) => {
+                    return Err(bad_request(BINDING_EXISTS))
+                }
+                Some(record) => record.proofs.push(proof),
+                None => state.avatars.push(AvatarRecord {
+                    avatar: payload.avatar,
+                    proofs: vec![proof],
+                }),
+            }
+        }
+        Action::Delete => {
+            for record in state.avatars.iter_mut() {
+                if record.avatar == payload.avatar {
+                    record.proofs.retain(|proof| {
+                        !(proof.platform == payload.platform && proof.identity == payload.identity)
+                    });
+                }
+            }
+            state.avatars.retain(|record| !record.proofs.is_empty());
+        }
+    }
+
+    Ok((StatusCode::CREATED, json!({})))
+}
+
+fn verify_base64_signature(
+    signature: &str,
+    payload: &Payload,
+) -> std::result::Result<(), (StatusCode, String)> {
+    let signature = base64_decode(signature).map_err(|_| bad_request(SIGNATURE_INVALID))?;
+    verify_signature(&signature, &payload.sign_payload, &payload.avatar)
+}
+
+/// Ethereum wallet signature must recover to the address in `identity`.
+fn verify_wallet_signature(
+    signature: &str,
+    payload: &Payload,
+) -> std::result::Result<(), (StatusCode, String)> {
+    let signature = base64_decode(signature).map_err(|_| bad_request(SIGNATURE_INVALID))?;
+    let recovered =
+        Secp256k1KeyPair::recover_from_personal_signature(&signature, &payload.sign_payload)
+            .map_err(|_| bad_request(SIGNATURE_INVALID))?;
+    let expected =
+        hex_decode(&payload.identity).map_err(|_| bad_request("invalid ethereum address"))?;
+    if eth_address_from_public_key(&recovered.pk).as_slice() != expected.as_slice() {
+        return Err(bad_request(WALLET_SIGNATURE_MISMATCH));
+    }
+    Ok(())
+}
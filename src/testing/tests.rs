@@ -0,0 +1,607 @@
+use super::MockServer;
+use crate::{
+    kv_service::KVProcedure,
+    proof_service::{
+        Action, Avatar, BatchOptions, Binding, Order, Page, Pagination, Platform, ProofProcedure,
+        QueryOptions, SortKey,
+    },
+    types::{Error, Result, ServerErrorKind},
+    util::{base64_encode, crypto::Secp256k1KeyPair, hex_encode},
+};
+use futures::TryStreamExt;
+use serde_json::json;
+
+fn new_avatar() -> Secp256k1KeyPair {
+    Secp256k1KeyPair::generate(&mut rand::rngs::OsRng)
+}
+
+/// Start a ProofService procedure and publish its post, signed by the procedure's avatar.
+async fn prepare_proof(
+    server: &MockServer,
+    action: Action,
+    avatar: Secp256k1KeyPair,
+    platform: Platform,
+    identity: &str,
+) -> Result<(ProofProcedure, String)> {
+    let mut procedure =
+        ProofProcedure::new(server.proof_endpoint(), action, avatar, platform, identity);
+    procedure.get_payload().await?;
+    let signature = procedure
+        .avatar
+        .personal_sign(procedure.sign_payload.as_ref().unwrap())?;
+    let post = procedure.post_content.as_ref().unwrap()["default"]
+        .replace("%SIG_BASE64%", &base64_encode(&signature));
+    let location = format!(
+        "https://twitter.com/{}/status/{}",
+        identity,
+        rand::random::<u32>()
+    );
+    server.publish_post(&location, &post);
+    Ok((procedure, location))
+}
+
+async fn bind(
+    server: &MockServer,
+    avatar: Secp256k1KeyPair,
+    platform: Platform,
+    identity: &str,
+) -> Result<()> {
+    let (mut procedure, location) =
+        prepare_proof(server, Action::Create, avatar, platform, identity).await?;
+    procedure.submit(location, None, None).await
+}
+
+async fn write_kv(
+    server: &MockServer,
+    avatar: Secp256k1KeyPair,
+    identity: &str,
+    patch: serde_json::Value,
+) -> Result<serde_json::Value> {
+    let mut procedure = KVProcedure::new(
+        server.kv_endpoint(),
+        Action::Create,
+        avatar,
+        Platform::Twitter,
+        identity,
+        patch,
+    );
+    procedure.get_payload().await?;
+    let signature = procedure
+        .avatar
+        .personal_sign(procedure.sign_payload.as_ref().unwrap())?;
+    let proofs = procedure.submit(signature).await?;
+    Ok(proofs
+        .into_iter()
+        .find(|proof| proof.identity == identity)
+        .map(|proof| proof.content)
+        .unwrap_or_default())
+}
+
+#[tokio::test]
+async fn test_bind_then_find() -> Result<()> {
+    let server = MockServer::start().await?;
+    let avatar = new_avatar();
+    let compressed = avatar.pk.serialize_compressed().to_vec();
+    bind(&server, avatar, Platform::Twitter, "alice").await?;
+
+    let avatars = server
+        .proof_service()
+        .find_by(Platform::Twitter, "alice", true)
+        .await?;
+    assert_eq!(1, avatars.len());
+    assert_eq!(compressed, avatars[0].avatar);
+    assert_eq!("alice", avatars[0].proofs[0].identity);
+    assert!(avatars[0].proofs[0].is_valid);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_kv_merge_patch() -> Result<()> {
+    let server = MockServer::start().await?;
+    let secret = new_avatar().sk.unwrap();
+    bind(
+        &server,
+        Secp256k1KeyPair::from_sk(secret),
+        Platform::Twitter,
+        "alice",
+    )
+    .await?;
+
+    let content = write_kv(
+        &server,
+        Secp256k1KeyPair::from_sk(secret),
+        "alice",
+        json!({"a": 1, "b": {"c": 2, "d": 3}}),
+    )
+    .await?;
+    assert_eq!(json!({"a": 1, "b": {"c": 2, "d": 3}}), content);
+    let content = write_kv(
+        &server,
+        Secp256k1KeyPair::from_sk(secret),
+        "alice",
+        json!({"b": {"c": null}, "e": "f"}),
+    )
+    .await?;
+    assert_eq!(json!({"a": 1, "b": {"d": 3}, "e": "f"}), content);
+
+    let by_identity = server
+        .kv_service()
+        .find_by_platform_identity(Platform::Twitter, "alice")
+        .await?;
+    assert_eq!(1, by_identity.len());
+    assert_eq!(content, by_identity[0].content);
+    let by_avatar = server
+        .kv_service()
+        .find_by_avatar(&Secp256k1KeyPair::from_sk(secret))
+        .await?;
+    assert_eq!(1, by_avatar.len());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_pagination() -> Result<()> {
+    let server = MockServer::start_with_page_size(1).await?;
+    bind(&server, new_avatar(), Platform::Twitter, "bob").await?;
+    bind(&server, new_avatar(), Platform::Twitter, "bob").await?;
+
+    let client = server.proof_service();
+    assert_eq!(
+        1,
+        client.find_by(Platform::Twitter, "bob", false).await?.len()
+    );
+    assert_eq!(
+        2,
+        client.find_by(Platform::Twitter, "bob", true).await?.len()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_rejections() -> Result<()> {
+    let server = MockServer::start().await?;
+    let secret = new_avatar().sk.unwrap();
+
+    // Post signed by another avatar.
+    let (mut procedure, _) = prepare_proof(
+        &server,
+        Action::Create,
+        Secp256k1KeyPair::from_sk(secret),
+        Platform::Twitter,
+        "carol",
+    )
+    .await?;
+    let forged = new_avatar().personal_sign(procedure.sign_payload.as_ref().unwrap())?;
+    let post = procedure.post_content.as_ref().unwrap()["default"]
+        .replace("%SIG_BASE64%", &base64_encode(&forged));
+    server.publish_post("https://twitter.com/carol/status/1", &post);
+    let err = procedure
+        .submit("https://twitter.com/carol/status/1".into(), None, None)
+        .await
+        .unwrap_err();
+    assert_eq!(
+        Some(&ServerErrorKind::SignatureMismatch),
+        err.server_error_kind()
+    );
+
+    // Post never published.
+    let err = procedure
+        .submit("https://twitter.com/carol/status/2".into(), None, None)
+        .await
+        .unwrap_err();
+    assert_eq!(
+        Some(&ServerErrorKind::ProofPostNotFound),
+        err.server_error_kind()
+    );
+
+    // KV of an identity not bound yet.
+    let mut kv = KVProcedure::new(
+        server.kv_endpoint(),
+        Action::Create,
+        Secp256k1KeyPair::from_sk(secret),
+        Platform::Twitter,
+        "carol",
+        json!({"a": 1}),
+    );
+    let err = kv.get_payload().await.unwrap_err();
+    assert_eq!(
+        Some(&ServerErrorKind::BindingNotFound),
+        err.server_error_kind()
+    );
+
+    // Binding twice.
+    bind(
+        &server,
+        Secp256k1KeyPair::from_sk(secret),
+        Platform::Twitter,
+        "carol",
+    )
+    .await?;
+    let err = bind(
+        &server,
+        Secp256k1KeyPair::from_sk(secret),
+        Platform::Twitter,
+        "carol",
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(
+        Some(&ServerErrorKind::AlreadyBound),
+        err.server_error_kind()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_unbind() -> Result<()> {
+    let server = MockServer::start().await?;
+    let secret = new_avatar().sk.unwrap();
+    bind(
+        &server,
+        Secp256k1KeyPair::from_sk(secret),
+        Platform::Twitter,
+        "dave",
+    )
+    .await?;
+    let (mut procedure, location) = prepare_proof(
+        &server,
+        Action::Delete,
+        Secp256k1KeyPair::from_sk(secret),
+        Platform::Twitter,
+        "dave",
+    )
+    .await?;
+    procedure.submit(location, None, None).await?;
+
+    let avatars = server
+        .proof_service()
+        .find_by(Platform::Twitter, "dave", true)
+        .await?;
+    assert!(avatars.is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_healthz() -> Result<()> {
+    let server = MockServer::start().await?;
+    let proof = server.proof_service().healthz().await?;
+    assert!(proof.supported_platforms().contains(&Platform::Twitter));
+    assert_eq!(Some("mock"), proof.environment.as_deref());
+    let kv = server.kv_service().healthz().await?;
+    assert_eq!(proof.hello, kv.hello);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_find_by_many() -> Result<()> {
+    let server = MockServer::start_with_page_size(1).await?;
+    let secret = new_avatar().sk.unwrap();
+    bind(
+        &server,
+        Secp256k1KeyPair::from_sk(secret),
+        Platform::Twitter,
+        "alice",
+    )
+    .await?;
+    bind(&server, new_avatar(), Platform::Twitter, "bob").await?;
+    bind(&server, new_avatar(), Platform::Twitter, "bob").await?;
+
+    let client = server.proof_service();
+    let found = client
+        .find_by_many(Platform::Twitter, &["alice", "Bob", "carol"])
+        .await?;
+    assert_eq!(3, found.len());
+    assert_eq!(1, found["alice"].len());
+    assert_eq!(2, found["Bob"].len());
+    assert!(found["carol"].is_empty());
+
+    // Split over several batches.
+    let mut many: Vec<String> = (0..120).map(|index| format!("user{}", index)).collect();
+    many.push("alice".into());
+    let found = client.find_by_many(Platform::Twitter, &many).await?;
+    assert_eq!(121, found.len());
+    assert_eq!(1, found["alice"].len());
+
+    // `nextid` identities match avatars whatever their public key encoding.
+    let avatar = Secp256k1KeyPair::from_sk(secret);
+    let uncompressed = format!("0x{}", hex_encode(&avatar.pk.serialize()));
+    let found = client
+        .find_by_many(Platform::NextID, &[uncompressed.as_str()])
+        .await?;
+    assert_eq!("alice", found[&uncompressed][0].proofs[0].identity);
+
+    let result = client
+        .find_by_many(Platform::Twitter, &["alice", "bob,carol"])
+        .await;
+    assert!(matches!(result, Err(Error::ValidationError(_))));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_find_by_many_with() -> Result<()> {
+    let server = MockServer::start_with_page_size(1).await?;
+    for _ in 0..3 {
+        bind(&server, new_avatar(), Platform::Twitter, "dave").await?;
+    }
+    let client = server.proof_service();
+    let options = BatchOptions {
+        max_identities: 1,
+        max_pages: 2,
+        ..Default::default()
+    };
+    let found = client
+        .find_by_many_with(Platform::Twitter, &["dave", "erin"], &options)
+        .await?;
+    // Pages past `max_pages` are left out.
+    assert_eq!(2, found["dave"].len());
+    assert!(found["erin"].is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_find_by_pages() -> Result<()> {
+    let server = MockServer::start_with_page_size(1).await?;
+    for _ in 0..3 {
+        bind(&server, new_avatar(), Platform::Twitter, "erin").await?;
+    }
+    let client = server.proof_service();
+
+    let pages: Vec<Page> = client
+        .find_by_pages(
+            Platform::Twitter,
+            "erin",
+            QueryOptions::new().start_page(2).max_pages(1),
+        )
+        .try_collect()
+        .await?;
+    assert_eq!(1, pages.len());
+    assert_eq!(
+        Pagination {
+            total: 3,
+            per: 1,
+            current: 2,
+            next: Some(3)
+        },
+        pages[0].pagination
+    );
+    assert_eq!(3, pages[0].pagination.pages());
+
+    let avatars: Vec<Avatar> = client
+        .find_by_stream(Platform::Twitter, "erin", QueryOptions::new())
+        .try_collect()
+        .await?;
+    assert_eq!(3, avatars.len());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_query_options() -> Result<()> {
+    let server = MockServer::start().await?;
+    let secret = new_avatar().sk.unwrap();
+    let frank = Secp256k1KeyPair::from_sk(secret)
+        .pk
+        .serialize_compressed()
+        .to_vec();
+    bind(
+        &server,
+        Secp256k1KeyPair::from_sk(secret),
+        Platform::Twitter,
+        "frank",
+    )
+    .await?;
+    bind(
+        &server,
+        Secp256k1KeyPair::from_sk(secret),
+        Platform::Github,
+        "frank",
+    )
+    .await?;
+    bind(&server, new_avatar(), Platform::Twitter, "frankie").await?;
+    let client = server.proof_service();
+    let find = |options: QueryOptions| client.find_by_with(Platform::Twitter, "frank", options);
+
+    assert_eq!(1, find(QueryOptions::new()).await?.len());
+    let fuzzy = find(QueryOptions::new().exact(false)).await?;
+    assert_eq!(2, fuzzy.len());
+    assert_eq!(frank, fuzzy[0].avatar);
+    let newest_first = find(
+        QueryOptions::new()
+            .exact(false)
+            .sort(SortKey::ActivatedAt)
+            .order(Order::Descending),
+    )
+    .await?;
+    assert_eq!(frank, newest_first[1].avatar);
+    let with_github = find(
+        QueryOptions::new()
+            .exact(false)
+            .only_valid_proofs()
+            .with_platform(Platform::Github),
+    )
+    .await?;
+    assert_eq!(1, with_github.len());
+    assert_eq!(frank, with_github[0].avatar);
+
+    let pages: Vec<Page> = client
+        .find_by_pages(
+            Platform::Twitter,
+            "frank",
+            QueryOptions::new().exact(false).page_size(1),
+        )
+        .try_collect()
+        .await?;
+    assert_eq!(2, pages.len());
+    assert_eq!(1, pages[0].pagination.per);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_find_by_avatar() -> Result<()> {
+    let server = MockServer::start().await?;
+    let secret = new_avatar().sk.unwrap();
+    bind(
+        &server,
+        Secp256k1KeyPair::from_sk(secret),
+        Platform::Twitter,
+        "grace",
+    )
+    .await?;
+    let client = server.proof_service();
+
+    // Same avatar, uncompressed.
+    let avatar = Secp256k1KeyPair::from_pk_hex(&hex_encode(
+        &Secp256k1KeyPair::from_sk(secret).pk.serialize(),
+    ))?;
+    let found = client.find_by_avatar(&avatar).await?.unwrap();
+    assert_eq!(avatar.pk.serialize_compressed().to_vec(), found.avatar);
+    assert_eq!("grace", found.proofs[0].identity);
+
+    assert!(client.find_by_avatar(&new_avatar()).await?.is_none());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_exists() -> Result<()> {
+    let server = MockServer::start().await?;
+    let secret = new_avatar().sk.unwrap();
+    bind(
+        &server,
+        Secp256k1KeyPair::from_sk(secret),
+        Platform::Twitter,
+        "heidi",
+    )
+    .await?;
+    let client = server.proof_service();
+    let avatar = Secp256k1KeyPair::from_sk(secret);
+
+    let binding = client.exists(Platform::Twitter, "Heidi", &avatar).await?;
+    assert_eq!(Binding::Valid, binding);
+    let binding = client.exists(Platform::Github, "heidi", &avatar).await?;
+    assert_eq!(Binding::NotBound, binding);
+    let binding = client
+        .exists(Platform::Twitter, "heidi", &new_avatar())
+        .await?;
+    assert!(!binding.is_bound());
+    Ok(())
+}
+
+/// Subscriber keeping every span and event as a `name field=value ...` line.
+#[cfg(feature = "tracing")]
+#[derive(Default)]
+struct RecordingSubscriber(std::sync::Mutex<Vec<String>>);
+
+#[cfg(feature = "tracing")]
+struct LineVisitor<'a>(&'a mut String);
+
+#[cfg(feature = "tracing")]
+impl tracing::field::Visit for LineVisitor<'_> {
+    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+        self.0.push_str(&format!(" {}={:?}", field.name(), value));
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl tracing::Subscriber for RecordingSubscriber {
+    fn enabled(&self, _: &tracing::Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+        let mut line = span.metadata().name().to_string();
+        span.record(&mut LineVisitor(&mut line));
+        let mut lines = self.0.lock().unwrap();
+        lines.push(line);
+        tracing::span::Id::from_u64(lines.len() as u64)
+    }
+
+    fn record(&self, span: &tracing::span::Id, values: &tracing::span::Record<'_>) {
+        let mut lines = self.0.lock().unwrap();
+        values.record(&mut LineVisitor(&mut lines[span.into_u64() as usize - 1]));
+    }
+
+    fn record_follows_from(&self, _: &tracing::span::Id, _: &tracing::span::Id) {}
+
+    fn event(&self, event: &tracing::Event<'_>) {
+        let mut line = event.metadata().name().to_string();
+        event.record(&mut LineVisitor(&mut line));
+        self.0.lock().unwrap().push(line);
+    }
+
+    fn enter(&self, _: &tracing::span::Id) {}
+
+    fn exit(&self, _: &tracing::span::Id) {}
+}
+
+#[cfg(feature = "tracing")]
+#[tokio::test]
+async fn test_spans_leave_out_secrets() -> Result<()> {
+    let subscriber = std::sync::Arc::new(RecordingSubscriber::default());
+    let _guard = tracing::subscriber::set_default(subscriber.clone());
+    let server = MockServer::start().await?;
+    let secret = new_avatar().sk.unwrap();
+
+    let mut procedure = ProofProcedure::new(
+        server.proof_endpoint(),
+        Action::Create,
+        Secp256k1KeyPair::from_sk(secret),
+        Platform::Twitter,
+        "alice",
+    );
+    procedure.get_payload().await?;
+    let proof_signature = procedure
+        .avatar
+        .personal_sign(procedure.sign_payload.as_ref().unwrap())?;
+    let post = procedure.post_content.as_ref().unwrap()["default"]
+        .replace("%SIG_BASE64%", &base64_encode(&proof_signature));
+    server.publish_post("https://twitter.com/alice/status/1", &post);
+    procedure
+        .submit("https://twitter.com/alice/status/1".into(), None, None)
+        .await?;
+
+    let mut kv = KVProcedure::new(
+        server.kv_endpoint(),
+        Action::Create,
+        Secp256k1KeyPair::from_sk(secret),
+        Platform::Twitter,
+        "alice",
+        json!({"a": 1}),
+    );
+    kv.get_payload().await?;
+    let kv_signature = kv.avatar.personal_sign(kv.sign_payload.as_ref().unwrap())?;
+    kv.submit(kv_signature.clone()).await?;
+
+    let lines = subscriber.0.lock().unwrap();
+    for name in [
+        "ProofProcedure.get_payload",
+        "ProofProcedure.submit",
+        "KVProcedure.get_payload",
+        "KVProcedure.submit",
+    ] {
+        assert!(lines.iter().any(|line| line.starts_with(name)), "{}", name);
+    }
+    let secrets = [
+        hex_encode(&secret.serialize()),
+        base64_encode(&proof_signature),
+        hex_encode(&proof_signature),
+        format!("{:?}", proof_signature),
+        base64_encode(&kv_signature),
+        hex_encode(&kv_signature),
+        format!("{:?}", kv_signature),
+        post,
+        procedure.sign_payload.clone().unwrap(),
+        kv.sign_payload.clone().unwrap(),
+    ];
+    for line in lines.iter() {
+        for secret in &secrets {
+            assert!(!line.contains(secret.as_str()), "{}", line);
+        }
+        for field in [
+            " signature=",
+            " avatar_signature=",
+            " post_content=",
+            " sk=",
+        ] {
+            assert!(!line.contains(field), "{}", line);
+        }
+    }
+    Ok(())
+}
@@ -1,21 +1,187 @@
+use http::{Method, StatusCode};
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("HTTP request error: {0}")]
     HttpError(#[from] hyper::Error),
+    #[error("Invalid HTTP request: {0}")]
+    InvalidRequest(#[from] http::Error),
     #[error("Hex parsing error: {0}")]
     HexError(#[from] hex::FromHexError),
     #[error("Secp256k1 error: {0}")]
     Secp256k1Error(#[from] libsecp256k1::Error),
     #[error("Base64 decode error: {0}")]
     Base64Error(#[from] base64::DecodeError),
+    #[error("Response body exceeds {0} bytes")]
+    BodyTooLarge(usize),
+    #[error("Unexpected response content type: {0}")]
+    UnexpectedContentType(String),
+    #[error("Request timed out after {0:?}")]
+    Timeout(std::time::Duration),
     #[error("Remote server error: {0}")]
-    ServerError(String),
+    ServerError(Box<ServerError>),
+    #[error("Local validation error: {0}")]
+    ValidationError(String),
     #[error("Error when parsing body: {0}")]
     JSONParsingError(#[from] serde_json::Error),
     #[error("Error when parsing URL: {0}")]
     URLParsingError(#[from] url::ParseError),
     #[error("Error when parsing int from string: {0}")]
     ParseIntError(#[from] std::num::ParseIntError),
+    #[error("Timestamp out of range: {0}")]
+    TimestampOutOfRange(i64),
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("No recorded interaction matches {0}")]
+    ReplayMismatch(String),
+    #[error("Invalid configuration: {0}")]
+    ConfigError(String),
+}
+
+impl Error {
+    /// Short label of this error, used in metrics.
+    pub(crate) fn label(&self) -> &'static str {
+        match self {
+            Error::HttpError(_) => "http",
+            Error::InvalidRequest(_) => "invalid_request",
+            Error::HexError(_) => "hex",
+            Error::Secp256k1Error(_) => "secp256k1",
+            Error::Base64Error(_) => "base64",
+            Error::BodyTooLarge(_) => "body_too_large",
+            Error::UnexpectedContentType(_) => "unexpected_content_type",
+            Error::Timeout(_) => "timeout",
+            Error::ServerError(_) => "server_error",
+            Error::ValidationError(_) => "validation",
+            Error::JSONParsingError(_) => "json",
+            Error::URLParsingError(_) => "url",
+            Error::ParseIntError(_) => "parse_int",
+            Error::TimestampOutOfRange(_) => "timestamp",
+            Error::IoError(_) => "io",
+            Error::ReplayMismatch(_) => "replay_mismatch",
+            Error::ConfigError(_) => "config",
+        }
+    }
+
+    /// Known failure reason, if this error comes from a server response.
+    pub fn server_error_kind(&self) -> Option<&ServerErrorKind> {
+        self.server_error().map(|err| &err.kind)
+    }
+
+    /// Remote server error detail, if this error comes from a server response.
+    pub fn server_error(&self) -> Option<&ServerError> {
+        match self {
+            Error::ServerError(err) => Some(err),
+            _ => None,
+        }
+    }
+
+    /// Server responded with `404 Not Found`.
+    pub fn is_not_found(&self) -> bool {
+        self.server_error().is_some_and(|err| err.is_not_found())
+    }
+
+    /// Server rejected the request with a `4xx` status.
+    pub fn is_client_error(&self) -> bool {
+        self.server_error().is_some_and(|err| err.is_client_error())
+    }
+
+    /// Sending the same request again later may succeed:
+    /// network errors, timeouts, `5xx`, `408` and `429` responses.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Error::HttpError(_) | Error::Timeout(_) => true,
+            Error::ServerError(err) => err.is_retryable(),
+            _ => false,
+        }
+    }
+}
+
+/// Error response returned by a remote ProofService / KVService server.
+#[derive(Debug, Clone)]
+pub struct ServerError {
+    /// HTTP status code of the response.
+    pub status: StatusCode,
+    /// `message` given by the server.
+    pub message: String,
+    /// Known failure reason recognized from `message`.
+    pub kind: ServerErrorKind,
+    /// Raw response body.
+    pub body: String,
+    /// HTTP method of the failed request.
+    pub method: Method,
+    /// URL path of the failed request (e.g. `/v1/proof`).
+    pub path: String,
+}
+
+impl ServerError {
+    /// `404 Not Found`
+    pub fn is_not_found(&self) -> bool {
+        self.status == StatusCode::NOT_FOUND
+    }
+
+    /// Any `4xx` status.
+    pub fn is_client_error(&self) -> bool {
+        self.status.is_client_error()
+    }
+
+    /// `5xx`, `408 Request Timeout` or `429 Too Many Requests`.
+    pub fn is_retryable(&self) -> bool {
+        self.status.is_server_error()
+            || self.status == StatusCode::REQUEST_TIMEOUT
+            || self.status == StatusCode::TOO_MANY_REQUESTS
+    }
+}
+
+/// Failure reasons known to be returned by ProofService / KVService.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ServerErrorKind {
+    /// Sign payload (`uuid` / `created_at`) is expired or unknown to the server.
+    /// Restart the procedure from `get_payload()`.
+    PayloadExpired,
+    /// Signature does not match the sign payload or the claimed public key.
+    SignatureMismatch,
+    /// Proof post (tweet, gist, etc.) cannot be found at the given proof location.
+    ProofPostNotFound,
+    /// This platform identity is already bound to an avatar.
+    AlreadyBound,
+    /// Avatar has no binding with this platform identity (e.g. when writing KV).
+    BindingNotFound,
+    /// Anything else, with the original server message.
+    Unknown(String),
+}
+
+impl ServerErrorKind {
+    /// Whether the procedure should be restarted from `get_payload()` to fix this.
+    pub fn requires_new_payload(&self) -> bool {
+        matches!(
+            self,
+            ServerErrorKind::PayloadExpired | ServerErrorKind::SignatureMismatch
+        )
+    }
+}
+
+impl std::fmt::Display for ServerErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use ServerErrorKind::*;
+        match self {
+            PayloadExpired => write!(f, "Sign payload expired, please start over."),
+            SignatureMismatch => write!(f, "Signature does not match the sign payload."),
+            ProofPostNotFound => write!(f, "Proof post not found, check if it is public."),
+            AlreadyBound => write!(f, "This identity is already bound to an avatar."),
+            BindingNotFound => write!(f, "This avatar has no binding with this identity."),
+            Unknown(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::fmt::Display for ServerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Status: {}, Error: {} ({} {})",
+            self.status, self.message, self.method, self.path
+        )
+    }
 }
 
 pub type Result<T> = core::result::Result<T, Error>;
@@ -0,0 +1,248 @@
+use crate::{
+    types::Result,
+    util::{
+        crypto::Secp256k1KeyPair,
+        hex_encode,
+        http::{Transport, TransportRequest, TransportResponse},
+        metrics::Service,
+    },
+};
+use async_trait::async_trait;
+use http::Method;
+use serde_json::Value;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Mutex,
+    time::Duration,
+};
+use tokio::time::Instant;
+
+/// Settings of a [CacheTransport].
+#[derive(Debug, Clone)]
+pub struct CacheConfig {
+    /// How long a response stays fresh.
+    pub ttl: Duration,
+    /// Max responses kept. The ones closest to expiry are evicted first.
+    pub max_entries: usize,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            ttl: Duration::from_secs(60),
+            max_entries: 1024,
+        }
+    }
+}
+
+/// Avatar or binding a request / response is about.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Subject {
+    /// Compressed public key, hex-encoded.
+    Avatar(String),
+    /// `(platform, identity)`, lowercased.
+    Identity(String, String),
+}
+
+/// (service, operation, full URL incl. endpoint and query)
+type Key = (Service, &'static str, String);
+
+struct Entry {
+    response: TransportResponse,
+    expires_at: Instant,
+    subjects: HashSet<Subject>,
+}
+
+#[derive(Default)]
+struct State {
+    entries: HashMap<Key, Entry>,
+    /// Bumped by every write, so that reads started before it are not cached.
+    generation: u64,
+}
+
+/// [Transport] caching successful `GET` responses of `inner` in memory.
+///
+/// Entries are keyed by endpoint, operation and query parameters.
+/// A successful write (e.g. [ProofProcedure::submit](crate::proof_service::ProofProcedure::submit)
+/// or [KVProcedure::submit](crate::kv_service::KVProcedure::submit)) sent through the same
+/// transport drops every entry about the written avatar or platform / identity,
+/// so readers never see stale data after their own writes.
+///
+/// # Examples
+/// ```rust
+/// # use std::{sync::Arc, time::Duration};
+/// # use nextid_sdk::proof_service::Endpoint;
+/// # use nextid_sdk::util::{cache::{CacheConfig, CacheTransport}, http::default_transport};
+/// let transport = CacheTransport::new(
+///     default_transport(),
+///     CacheConfig { ttl: Duration::from_secs(10), ..Default::default() },
+/// );
+/// let client = Endpoint::Production.with_transport(Arc::new(transport));
+/// ```
+pub struct CacheTransport<T> {
+    inner: T,
+    config: CacheConfig,
+    state: Mutex<State>,
+}
+
+impl<T> CacheTransport<T> {
+    pub fn new(inner: T, config: CacheConfig) -> Self {
+        Self {
+            inner,
+            config,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// Drop all cached responses.
+    pub fn clear(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.entries.clear();
+        state.generation += 1;
+    }
+
+    fn get(&self, key: &Key) -> (Option<TransportResponse>, u64) {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        state.entries.retain(|_, entry| entry.expires_at > now);
+        let cached = state.entries.get(key).map(|entry| entry.response.clone());
+        (cached, state.generation)
+    }
+
+    fn insert(
+        &self,
+        key: Key,
+        response: TransportResponse,
+        subjects: HashSet<Subject>,
+        generation: u64,
+    ) {
+        let mut state = self.state.lock().unwrap();
+        if state.generation != generation || self.config.max_entries == 0 {
+            return;
+        }
+        if !state.entries.contains_key(&key) && state.entries.len() >= self.config.max_entries {
+            let oldest = state
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.expires_at)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                state.entries.remove(&oldest);
+            }
+        }
+        state.entries.insert(
+            key,
+            Entry {
+                response,
+                expires_at: Instant::now() + self.config.ttl,
+                subjects,
+            },
+        );
+    }
+
+    fn invalidate(&self, subjects: &HashSet<Subject>) {
+        let mut state = self.state.lock().unwrap();
+        state.generation += 1;
+        state
+            .entries
+            .retain(|_, entry| entry.subjects.is_disjoint(subjects));
+    }
+}
+
+#[async_trait]
+impl<T: Transport> Transport for CacheTransport<T> {
+    async fn send(&self, request: TransportRequest) -> Result<TransportResponse> {
+        if request.method != Method::GET {
+            let written = request_subjects(&request);
+            let response = self.inner.send(request).await?;
+            if response.status.is_success() {
+                self.invalidate(&written);
+            }
+            return Ok(response);
+        }
+
+        let key = (
+            request.operation.service,
+            request.operation.name,
+            request.url.to_string(),
+        );
+        let (cached, generation) = self.get(&key);
+        if let Some(response) = cached {
+            #[cfg(feature = "tracing")]
+            tracing::debug!("cache hit");
+            return Ok(response);
+        }
+
+        let mut subjects = request_subjects(&request);
+        let response = self.inner.send(request).await?;
+        if response.status.is_success() {
+            if let Ok(body) = serde_json::from_slice::<Value>(&response.body) {
+                collect_subjects(&body, &mut subjects);
+            }
+            self.insert(key, response.clone(), subjects, generation);
+        }
+        Ok(response)
+    }
+}
+
+/// Subjects mentioned in query parameters and JSON body of `request`.
+fn request_subjects(request: &TransportRequest) -> HashSet<Subject> {
+    let mut subjects = HashSet::new();
+    let query: serde_json::Map<String, Value> = request
+        .url
+        .query_pairs()
+        .map(|(name, value)| (name.into_owned(), Value::String(value.into_owned())))
+        .collect();
+    // Batch queries list comma-separated identities: each of them is a subject.
+    let identities: Vec<String> = match query.get("identity") {
+        Some(Value::String(identity)) => identity.split(',').map(str::to_string).collect(),
+        _ => vec![],
+    };
+    for identity in identities {
+        let mut query = query.clone();
+        query.insert("identity".to_string(), Value::String(identity));
+        collect_subjects(&Value::Object(query), &mut subjects);
+    }
+    collect_subjects(&Value::Object(query), &mut subjects);
+    if let Ok(body) = serde_json::from_slice::<Value>(&request.body) {
+        collect_subjects(&body, &mut subjects);
+    }
+    subjects
+}
+
+/// Walk through `value`, picking up avatars (`avatar` / `public_key`) and `platform` / `identity` pairs.
+fn collect_subjects(value: &Value, subjects: &mut HashSet<Subject>) {
+    match value {
+        Value::Array(values) => values
+            .iter()
+            .for_each(|value| collect_subjects(value, subjects)),
+        Value::Object(object) => {
+            for field in ["avatar", "public_key"] {
+                if let Some(Value::String(avatar)) = object.get(field) {
+                    subjects.insert(avatar_subject(avatar));
+                }
+            }
+            if let (Some(Value::String(platform)), Some(Value::String(identity))) =
+                (object.get("platform"), object.get("identity"))
+            {
+                let platform = platform.to_lowercase();
+                if platform == "nextid" {
+                    subjects.insert(avatar_subject(identity));
+                }
+                subjects.insert(Subject::Identity(platform, identity.to_lowercase()));
+            }
+            object
+                .values()
+                .for_each(|value| collect_subjects(value, subjects));
+        }
+        _ => {}
+    }
+}
+
+/// Same avatar in compressed or uncompressed form gives the same subject.
+fn avatar_subject(avatar: &str) -> Subject {
+    match Secp256k1KeyPair::from_pk_hex(avatar) {
+        Ok(pair) => Subject::Avatar(hex_encode(&pair.pk.serialize_compressed())),
+        Err(_) => Subject::Avatar(avatar.to_lowercase()),
+    }
+}
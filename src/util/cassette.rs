@@ -0,0 +1,230 @@
+use crate::{
+    types::{Error, Result},
+    util::http::{Transport, TransportRequest, TransportResponse},
+};
+use async_trait::async_trait;
+use http::{HeaderMap, HeaderName, HeaderValue, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+use url::Url;
+
+/// Content of a cassette file: request / response pairs in the order they happened.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Cassette {
+    pub interactions: Vec<Interaction>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Interaction {
+    pub request: RecordedRequest,
+    pub response: RecordedResponse,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub url: String,
+    /// See [RecordedResponse::body].
+    #[serde(default)]
+    pub body: Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedResponse {
+    pub status: u16,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// JSON body as-is, other bodies as a string, `null` if empty.
+    #[serde(default)]
+    pub body: Value,
+}
+
+impl Cassette {
+    /// Load a cassette file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+    }
+
+    /// Write this cassette to `path`, pretty-printed.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        Ok(std::fs::write(path, serde_json::to_vec_pretty(self)?)?)
+    }
+}
+
+fn body_to_value(body: &[u8]) -> Value {
+    if body.is_empty() {
+        return Value::Null;
+    }
+    serde_json::from_slice(body)
+        .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(body).into_owned()))
+}
+
+fn value_to_body(value: &Value) -> Vec<u8> {
+    match value {
+        Value::Null => vec![],
+        Value::String(text) => text.as_bytes().to_vec(),
+        json => json.to_string().into_bytes(),
+    }
+}
+
+/// [Transport] passing requests to `inner` and writing every interaction to a cassette file,
+/// to be served later by a [ReplayTransport].
+/// The file is rewritten after each interaction.
+///
+/// # Examples
+/// ```rust,no_run
+/// # use std::sync::Arc;
+/// # use nextid_sdk::proof_service::Endpoint;
+/// # use nextid_sdk::util::{cassette::RecordingTransport, http::default_transport};
+/// let transport = RecordingTransport::new(default_transport(), "fixtures/my_test.json");
+/// let client = Endpoint::Staging.with_transport(Arc::new(transport));
+/// ```
+pub struct RecordingTransport<T> {
+    inner: T,
+    path: PathBuf,
+    cassette: Mutex<Cassette>,
+}
+
+impl<T> RecordingTransport<T> {
+    pub fn new(inner: T, path: impl Into<PathBuf>) -> Self {
+        Self {
+            inner,
+            path: path.into(),
+            cassette: Mutex::new(Cassette::default()),
+        }
+    }
+
+    /// Interactions recorded so far.
+    pub fn cassette(&self) -> Cassette {
+        self.cassette.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl<T: Transport> Transport for RecordingTransport<T> {
+    async fn send(&self, request: TransportRequest) -> Result<TransportResponse> {
+        let recorded_request = RecordedRequest {
+            method: request.method.to_string(),
+            url: request.url.to_string(),
+            body: body_to_value(&request.body),
+        };
+        let response = self.inner.send(request).await?;
+        let headers = response
+            .headers
+            .iter()
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+            .collect();
+        let mut cassette = self.cassette.lock().unwrap();
+        cassette.interactions.push(Interaction {
+            request: recorded_request,
+            response: RecordedResponse {
+                status: response.status.as_u16(),
+                headers,
+                body: body_to_value(&response.body),
+            },
+        });
+        cassette.save(&self.path)?;
+        Ok(response)
+    }
+}
+
+/// [Transport] answering from a [Cassette] instead of the network.
+///
+/// A request is served by the first unused interaction with the same method, path,
+/// query parameters and body (JSON bodies are compared as values).
+/// The host is ignored, so a cassette recorded against one endpoint replays against any other.
+/// Each interaction is served once; a request with no match fails with [Error::ReplayMismatch].
+///
+/// # Examples
+/// ```rust
+/// # use std::sync::Arc;
+/// # use nextid_sdk::proof_service::Endpoint;
+/// # use nextid_sdk::util::cassette::ReplayTransport;
+/// let transport = ReplayTransport::from_file("fixtures/proof_service.json").unwrap();
+/// let client = Endpoint::Staging.with_transport(Arc::new(transport));
+/// ```
+pub struct ReplayTransport {
+    interactions: Vec<Interaction>,
+    used: Mutex<Vec<bool>>,
+}
+
+impl ReplayTransport {
+    pub fn new(cassette: Cassette) -> Self {
+        let used = vec![false; cassette.interactions.len()];
+        Self {
+            interactions: cassette.interactions,
+            used: Mutex::new(used),
+        }
+    }
+
+    /// Replay a cassette file.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self::new(Cassette::load(path)?))
+    }
+
+    /// Number of interactions not served yet.
+    pub fn remaining(&self) -> usize {
+        self.used
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|used| !**used)
+            .count()
+    }
+
+    fn matches(recorded: &RecordedRequest, request: &TransportRequest, body: &Value) -> bool {
+        let Ok(url) = Url::parse(&recorded.url) else {
+            return false;
+        };
+        recorded.method == request.method.as_str()
+            && url.path() == request.url.path()
+            && sorted_query(&url) == sorted_query(&request.url)
+            && recorded.body == *body
+    }
+}
+
+fn sorted_query(url: &Url) -> Vec<(String, String)> {
+    let mut query: Vec<(String, String)> = url.query_pairs().into_owned().collect();
+    query.sort();
+    query
+}
+
+#[async_trait]
+impl Transport for ReplayTransport {
+    async fn send(&self, request: TransportRequest) -> Result<TransportResponse> {
+        let body = body_to_value(&request.body);
+        let mut used = self.used.lock().unwrap();
+        let index = self
+            .interactions
+            .iter()
+            .zip(used.iter())
+            .position(|(interaction, used)| {
+                !used && Self::matches(&interaction.request, &request, &body)
+            })
+            .ok_or_else(|| {
+                Error::ReplayMismatch(format!("{} {} {}", request.method, request.url, body))
+            })?;
+        used[index] = true;
+
+        let recorded = &self.interactions[index].response;
+        let mut headers = HeaderMap::new();
+        for (name, value) in recorded.headers.iter() {
+            if let (Ok(name), Ok(value)) = (
+                HeaderName::from_bytes(name.as_bytes()),
+                HeaderValue::from_str(value),
+            ) {
+                headers.insert(name, value);
+            }
+        }
+        Ok(TransportResponse {
+            status: StatusCode::from_u16(recorded.status).map_err(http::Error::from)?,
+            headers,
+            body: value_to_body(&recorded.body),
+        })
+    }
+}
@@ -43,11 +43,7 @@ impl Secp256k1KeyPair {
     /// # assert_eq!(hex!("04c7cacde73af939c35d527b34e0556ea84bab27e6c0ed7c6c59be70f6d2db59c206b23529977117dc8a5d61fa848f94950422b79d1c142bcf623862e49f9e6575"), pair.pk.serialize());
     /// ```
     pub fn from_pk_hex(pk_hex: &str) -> Result<Self> {
-        let hex = if pk_hex.starts_with("0x") {
-            &pk_hex[2..]
-        } else {
-            pk_hex
-        };
+        let hex = pk_hex.strip_prefix("0x").unwrap_or(pk_hex);
         let pk_bytes = hex_decode(hex)?;
         Self::from_pk_vec(&pk_bytes)
     }
@@ -99,11 +95,7 @@ impl Secp256k1KeyPair {
     /// let keypair = Secp256k1KeyPair::from_sk_hex(secret_key_hexstring);
     /// ```
     pub fn from_sk_hex(sk_hex: &str) -> Result<Self> {
-        let hex = if sk_hex.starts_with("0x") {
-            &sk_hex[2..]
-        } else {
-            sk_hex
-        };
+        let hex = sk_hex.strip_prefix("0x").unwrap_or(sk_hex);
         let sk_bytes = hex_decode(hex)?;
         Self::from_sk_vec(sk_bytes)
     }
@@ -196,8 +188,10 @@ impl Secp256k1KeyPair {
 
         let mut recovery_id = sig_r_s_recovery
             .get(64)
-            .ok_or_else(|| Error::Secp256k1Error(libsecp256k1::Error::InvalidInputLength))?
-            .clone();
+            .copied()
+            .ok_or(Error::Secp256k1Error(
+                libsecp256k1::Error::InvalidInputLength,
+            ))?;
 
         if recovery_id == 27 || recovery_id == 28 {
             recovery_id -= 27;
@@ -0,0 +1,184 @@
+use crate::{
+    types::{Error, Result},
+    util::http::{canonical_base_url, relative_to, Transport, TransportRequest, TransportResponse},
+};
+use async_trait::async_trait;
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+use tokio::time::Instant;
+use url::Url;
+
+/// Order in which backends are tried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Selection {
+    /// Always start from the first healthy backend, in the given order.
+    Priority,
+    /// Spread requests over healthy backends in turn.
+    RoundRobin,
+}
+
+/// Settings of a [FailoverTransport].
+#[derive(Debug, Clone)]
+pub struct FailoverPolicy {
+    pub selection: Selection,
+    /// Consecutive failures before a backend is considered unhealthy.
+    pub failure_threshold: u32,
+    /// How long an unhealthy backend is skipped before getting another chance.
+    pub cooldown: Duration,
+}
+
+impl Default for FailoverPolicy {
+    fn default() -> Self {
+        Self {
+            selection: Selection::Priority,
+            failure_threshold: 3,
+            cooldown: Duration::from_secs(30),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Health {
+    consecutive_failures: u32,
+    unhealthy_until: Option<Instant>,
+}
+
+/// [Transport] spreading requests of one endpoint over several backends serving the same API.
+///
+/// Requests whose URL starts with one of `backends` are sent to the backends in
+/// [Selection] order, moving on to the next one on network errors, timeouts and `5xx` responses
+/// (only on connection failures for non-idempotent requests, which may have been applied).
+/// Health is tracked passively: after [FailoverPolicy::failure_threshold] consecutive failures
+/// a backend is skipped for [FailoverPolicy::cooldown], unless no healthy backend is left.
+/// Other requests pass through untouched.
+///
+/// The backend that served a response is reported in the request's [RequestRecord],
+/// hence in [RequestMetrics::backend](crate::util::metrics::RequestMetrics::backend)
+/// and in the `nextid.http` tracing span.
+///
+/// # Examples
+/// ```rust
+/// # use std::sync::Arc;
+/// # use nextid_sdk::proof_service::Endpoint;
+/// # use nextid_sdk::util::{failover::{FailoverPolicy, FailoverTransport}, http::default_transport};
+/// let replica = Endpoint::Custom("https://proof-service.example.com".into());
+/// let transport = FailoverTransport::new(
+///     default_transport(),
+///     vec![replica.base_url(), Endpoint::Production.base_url()],
+///     FailoverPolicy::default(),
+/// );
+/// let client = replica.with_transport(Arc::new(transport));
+/// ```
+pub struct FailoverTransport<T> {
+    inner: T,
+    backends: Vec<String>,
+    policy: FailoverPolicy,
+    health: Mutex<Vec<Health>>,
+    next: AtomicUsize,
+}
+
+impl<T> FailoverTransport<T> {
+    /// `backends` are base URLs, as given by `Endpoint::base_url()`.
+    pub fn new(inner: T, backends: Vec<String>, policy: FailoverPolicy) -> Self {
+        let backends: Vec<String> = backends
+            .iter()
+            .map(|backend| canonical_base_url(backend))
+            .collect();
+        Self {
+            inner,
+            health: Mutex::new(vec![Health::default(); backends.len()]),
+            backends,
+            policy,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// Path and query of `url` relative to the backend it targets.
+    fn relative<'u>(&self, url: &'u Url) -> Option<&'u str> {
+        self.backends
+            .iter()
+            .find_map(|backend| relative_to(url, backend))
+    }
+
+    /// Backend indexes in the order to try: healthy ones first.
+    fn order(&self) -> Vec<usize> {
+        let count = self.backends.len();
+        let start = match self.policy.selection {
+            Selection::Priority => 0,
+            Selection::RoundRobin => self.next.fetch_add(1, Ordering::Relaxed) % count,
+        };
+        let now = Instant::now();
+        let health = self.health.lock().unwrap();
+        let (healthy, unhealthy): (Vec<usize>, Vec<usize>) = (0..count)
+            .map(|offset| (start + offset) % count)
+            .partition(|index| {
+                health[*index]
+                    .unhealthy_until
+                    .is_none_or(|until| until <= now)
+            });
+        healthy.into_iter().chain(unhealthy).collect()
+    }
+
+    fn record_health(&self, index: usize, success: bool) {
+        let mut health = self.health.lock().unwrap();
+        let health = &mut health[index];
+        if success {
+            *health = Health::default();
+            return;
+        }
+        health.consecutive_failures += 1;
+        if health.consecutive_failures >= self.policy.failure_threshold.max(1) {
+            health.unhealthy_until = Some(Instant::now() + self.policy.cooldown);
+            #[cfg(feature = "tracing")]
+            tracing::warn!(backend = %self.backends[index], "backend marked unhealthy");
+        }
+    }
+}
+
+#[async_trait]
+impl<T: Transport> Transport for FailoverTransport<T> {
+    async fn send(&self, request: TransportRequest) -> Result<TransportResponse> {
+        let Some(relative) = self.relative(&request.url).map(str::to_string) else {
+            return self.inner.send(request).await;
+        };
+        let idempotent = request.method.is_idempotent();
+        let record = request.record.clone();
+
+        let order = self.order();
+        for (attempt, index) in order.iter().copied().enumerate() {
+            let backend = &self.backends[index];
+            let mut request = request.clone();
+            request.url = Url::parse(&format!("{}{}", backend, relative))?;
+            let result = self.inner.send(request).await;
+            // Other errors (e.g. an oversized body) say nothing about the backend's health.
+            let failed = match &result {
+                Ok(response) => Some(response.status.is_server_error()),
+                Err(Error::HttpError(_) | Error::Timeout(_)) => Some(true),
+                Err(_) => None,
+            };
+            if let Some(failed) = failed {
+                self.record_health(index, !failed);
+            }
+            let may_fail_over = match &result {
+                _ if failed != Some(true) => false,
+                _ if idempotent => true,
+                Err(Error::HttpError(err)) => err.is_connect(),
+                _ => false,
+            };
+            if !may_fail_over || attempt + 1 == order.len() {
+                if result.is_ok() {
+                    record.set_backend(backend.as_str());
+                }
+                return result;
+            }
+            #[cfg(feature = "tracing")]
+            tracing::debug!(%backend, "failing over");
+        }
+        unreachable!("the URL matched a backend, so there is at least one")
+    }
+}
@@ -1,62 +1,561 @@
-use crate::types::{Error, Result};
-use http::Response;
-use hyper::{body::HttpBody, client::HttpConnector, Body, Client, Method, Request, StatusCode};
-use hyper_tls::HttpsConnector;
+use crate::{
+    types::{Error, Result, ServerError, ServerErrorKind},
+    util::{
+        metrics::{MetricsRecorder, Operation, RequestMetrics},
+        retry::{RetryPolicy, RetryTransport},
+    },
+};
+use async_trait::async_trait;
+use http::{header::CONTENT_TYPE, HeaderMap, HeaderValue, Method, StatusCode};
+use hyper::{body::HttpBody, client::HttpConnector, Body, Client, Request};
 use serde::Deserialize;
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use url::Url;
 
 #[derive(Deserialize)]
-pub struct ErrorResponse {
+pub(crate) struct ErrorResponse {
     pub message: String,
 }
 
-pub async fn request<T>(method: Method, uri: &url::Url, request_body: Body) -> Result<T>
+/// A single HTTP request issued by the SDK.
+/// More fields may be added: build one with [TransportRequest::new].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct TransportRequest {
+    pub method: Method,
+    pub url: Url,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+    /// SDK operation this request belongs to.
+    pub operation: Operation,
+    /// How this request was served, filled in by the transports it goes through.
+    pub record: RequestRecord,
+}
+
+/// Per-request record filled in by transports, e.g. the backend picked by a
+/// [FailoverTransport](crate::util::failover::FailoverTransport).
+/// Clones of a [TransportRequest] share it, so it can be read once the request is sent.
+#[derive(Debug, Clone, Default)]
+pub struct RequestRecord {
+    backend: Arc<Mutex<Option<String>>>,
+}
+
+impl RequestRecord {
+    /// Base URL that served the response, if reported.
+    pub fn backend(&self) -> Option<String> {
+        self.backend.lock().unwrap().clone()
+    }
+
+    /// Report the base URL that served the response.
+    pub fn set_backend(&self, backend: impl Into<String>) {
+        *self.backend.lock().unwrap() = Some(backend.into());
+    }
+}
+
+impl TransportRequest {
+    /// Request without headers nor body.
+    /// # Examples
+    /// ```rust
+    /// # use http::Method;
+    /// # use nextid_sdk::util::{http::TransportRequest, metrics::{Operation, Service}};
+    /// let operation = Operation {
+    ///     service: Service::ProofService,
+    ///     endpoint: "custom",
+    ///     name: "healthz",
+    /// };
+    /// let url = "https://proof-service.example.com/healthz".parse().unwrap();
+    /// let mut request = TransportRequest::new(Method::GET, url, operation);
+    /// request.headers.insert("Accept", "application/json".parse().unwrap());
+    /// ```
+    pub fn new(method: Method, url: Url, operation: Operation) -> Self {
+        Self {
+            method,
+            url,
+            headers: HeaderMap::new(),
+            body: vec![],
+            operation,
+            record: RequestRecord::default(),
+        }
+    }
+}
+
+/// Raw HTTP response returned by a [Transport].
+#[derive(Debug, Clone)]
+pub struct TransportResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+}
+
+/// Sends HTTP requests on behalf of ProofService / KVService calls.
+/// Implement this to plug in your own HTTP client (or an in-memory fake for tests).
+///
+/// # Examples
+/// ```rust
+/// # use nextid_sdk::{types::Result, util::http::{Transport, TransportRequest, TransportResponse}};
+/// # use http::{HeaderMap, StatusCode};
+/// struct AlwaysEmpty;
+///
+/// #[async_trait::async_trait]
+/// impl Transport for AlwaysEmpty {
+///     async fn send(&self, _request: TransportRequest) -> Result<TransportResponse> {
+///         Ok(TransportResponse {
+///             status: StatusCode::OK,
+///             headers: HeaderMap::new(),
+///             body: b"{}".to_vec(),
+///         })
+///     }
+/// }
+/// ```
+#[async_trait]
+pub trait Transport: Send + Sync {
+    /// Send `request` and return the response as-is, whatever its status code is.
+    async fn send(&self, request: TransportRequest) -> Result<TransportResponse>;
+}
+
+#[async_trait]
+impl<T: Transport + ?Sized> Transport for Arc<T> {
+    async fn send(&self, request: TransportRequest) -> Result<TransportResponse> {
+        self.as_ref().send(request).await
+    }
+}
+
+/// Default deadline of a whole API call, retries included.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+/// Default timeout of establishing a TCP connection.
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Default upper limit of a response body (16 MiB).
+pub const DEFAULT_MAX_BODY_SIZE: usize = 16 * 1024 * 1024;
+
+/// Connection pool and protocol settings of [HyperTransport].
+#[derive(Debug, Clone)]
+pub struct HttpConfig {
+    /// Timeout of establishing a TCP connection. `None` waits forever.
+    /// Fixed once the transport is built: `with_timeout()` of the service clients
+    /// only overrides the deadline of the whole call.
+    pub connect_timeout: Option<Duration>,
+    /// Max idle connections kept per host.
+    pub pool_max_idle_per_host: usize,
+    /// How long an idle connection stays in the pool. `None` keeps it forever.
+    pub pool_idle_timeout: Option<Duration>,
+    /// Speak HTTP/2 only, without HTTP/1.1 fallback.
+    /// Make sure the servers you talk to accept HTTP/2 with prior knowledge.
+    pub http2_only: bool,
+    /// Interval of HTTP/2 PING frames keeping connections alive. `None` disables it.
+    pub http2_keep_alive_interval: Option<Duration>,
+    /// Use adaptive flow control for HTTP/2 connections.
+    pub http2_adaptive_window: bool,
+    /// Responses with a larger body are dropped with [Error::BodyTooLarge].
+    pub max_body_size: usize,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            connect_timeout: Some(DEFAULT_CONNECT_TIMEOUT),
+            pool_max_idle_per_host: usize::MAX,
+            pool_idle_timeout: Some(Duration::from_secs(90)),
+            http2_only: false,
+            http2_keep_alive_interval: None,
+            http2_adaptive_window: false,
+            max_body_size: DEFAULT_MAX_BODY_SIZE,
+        }
+    }
+}
+
+#[cfg(not(any(feature = "native-tls", feature = "__rustls")))]
+compile_error!(
+    "Enable one TLS backend: `native-tls`, `rustls-webpki-roots` or `rustls-native-roots`."
+);
+
+#[cfg(feature = "__rustls")]
+type HttpsConnector = hyper_rustls::HttpsConnector<HttpConnector>;
+#[cfg(all(feature = "native-tls", not(feature = "__rustls")))]
+type HttpsConnector = hyper_tls::HttpsConnector<HttpConnector>;
+
+/// Wrap `http` with the TLS backend chosen by cargo features.
+/// rustls wins if both backends are enabled.
+#[cfg(feature = "__rustls")]
+fn https_connector(http: HttpConnector) -> HttpsConnector {
+    let builder = hyper_rustls::HttpsConnectorBuilder::new();
+    #[cfg(feature = "rustls-native-roots")]
+    let builder = builder.with_native_roots();
+    #[cfg(not(feature = "rustls-native-roots"))]
+    let builder = builder.with_webpki_roots();
+    builder
+        .https_or_http()
+        .enable_http1()
+        .enable_http2()
+        .wrap_connector(http)
+}
+
+#[cfg(all(feature = "native-tls", not(feature = "__rustls")))]
+fn https_connector(http: HttpConnector) -> HttpsConnector {
+    HttpsConnector::new_with_connector(http)
+}
+
+/// Default [Transport] based on `hyper`.
+/// Connections are pooled and shared by all clones of the same instance.
+#[derive(Clone)]
+pub struct HyperTransport {
+    client: Client<HttpsConnector>,
+    connect_timeout: Option<Duration>,
+    max_body_size: usize,
+}
+
+impl HyperTransport {
+    pub fn new() -> Self {
+        Self::with_config(&HttpConfig::default())
+    }
+
+    /// Build a transport with customized connection pool settings.
+    pub fn with_config(config: &HttpConfig) -> Self {
+        let mut http = HttpConnector::new();
+        http.enforce_http(false);
+        http.set_connect_timeout(config.connect_timeout);
+        let https = https_connector(http);
+        let client = Client::builder()
+            .pool_max_idle_per_host(config.pool_max_idle_per_host)
+            .pool_idle_timeout(config.pool_idle_timeout)
+            .http2_only(config.http2_only)
+            .http2_keep_alive_interval(config.http2_keep_alive_interval)
+            .http2_adaptive_window(config.http2_adaptive_window)
+            .build::<_, Body>(https);
+        Self {
+            client,
+            connect_timeout: config.connect_timeout,
+            max_body_size: config.max_body_size,
+        }
+    }
+
+    /// Tell connect timeouts apart from other `hyper` errors.
+    /// `elapsed` is the time spent on the request so far.
+    fn map_error(&self, err: hyper::Error, elapsed: Duration) -> Error {
+        if self.connect_timeout.is_none() {
+            return err.into();
+        }
+        let mut source = std::error::Error::source(&err);
+        while let Some(cause) = source {
+            if let Some(io_err) = cause.downcast_ref::<std::io::Error>() {
+                if io_err.kind() == std::io::ErrorKind::TimedOut {
+                    return Error::Timeout(elapsed);
+                }
+            }
+            source = cause.source();
+        }
+        err.into()
+    }
+}
+
+impl Default for HyperTransport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Transport for HyperTransport {
+    async fn send(&self, request: TransportRequest) -> Result<TransportResponse> {
+        let mut builder = Request::builder()
+            .method(request.method)
+            .uri(request.url.as_str());
+        if let Some(headers) = builder.headers_mut() {
+            headers.extend(request.headers);
+        }
+        let started = Instant::now();
+        let response = self
+            .client
+            .request(builder.body(request.body.into())?)
+            .await
+            .map_err(|err| self.map_error(err, started.elapsed()))?;
+
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = read_body(response.into_body(), self.max_body_size).await?;
+        Ok(TransportResponse {
+            status,
+            headers,
+            body,
+        })
+    }
+}
+
+/// Read the whole `body`, giving up once it grows over `limit` bytes.
+pub(crate) async fn read_body(mut body: Body, limit: usize) -> Result<Vec<u8>> {
+    if body.size_hint().lower() > limit as u64 {
+        return Err(Error::BodyTooLarge(limit));
+    }
+    let mut body_bytes: Vec<u8> = vec![];
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk?;
+        if body_bytes.len() + chunk.len() > limit {
+            return Err(Error::BodyTooLarge(limit));
+        }
+        body_bytes.extend_from_slice(&chunk);
+    }
+    Ok(body_bytes)
+}
+
+/// `base_url` in the form of request URLs: lowercase host, no default port nor trailing slash.
+pub(crate) fn canonical_base_url(base_url: &str) -> String {
+    let base_url = Url::parse(base_url)
+        .map(|url| url.to_string())
+        .unwrap_or_else(|_| base_url.to_string());
+    base_url.trim_end_matches('/').to_string()
+}
+
+/// Path and query of `url` if it targets the server rooted at `base_url` (in canonical form).
+pub(crate) fn relative_to<'u>(url: &'u Url, base_url: &str) -> Option<&'u str> {
+    let rest = url.as_str().strip_prefix(base_url)?;
+    (rest.is_empty() || rest.starts_with('/') || rest.starts_with('?')).then_some(rest)
+}
+
+/// [Transport] used when none is given explicitly.
+/// Failed queries are retried with [RetryPolicy::default()].
+pub fn default_transport() -> Arc<dyn Transport> {
+    Arc::new(RetryTransport::new(
+        HyperTransport::new(),
+        RetryPolicy::default(),
+    ))
+}
+
+/// Per-call settings of [request()].
+pub(crate) struct Call<'a> {
+    pub transport: &'a dyn Transport,
+    pub operation: Operation,
+    /// Give up with [Error::Timeout] once exceeded.
+    pub timeout: Option<Duration>,
+    pub metrics: Option<&'a dyn MetricsRecorder>,
+}
+
+/// Send a JSON request through `call.transport`.
+pub(crate) async fn request<T>(
+    call: Call<'_>,
+    method: Method,
+    uri: &Url,
+    request_body: Vec<u8>,
+) -> Result<T>
 where
     T: for<'de> Deserialize<'de>,
 {
-    let client = new_client();
-    let mut response = client
-        .request(
-            Request::builder()
-                .method(method)
-                .uri(uri.to_string().parse::<http::Uri>().unwrap())
-                .header("Accept", "application/json")
-                .header("Content-Type", "application/json")
-                .header("User-Agent", "NextID-SDK-Rust/0.1.0")
-                .body(request_body)
-                .unwrap(),
+    send_json(call, method, uri, request_body, parse_response).await
+}
+
+/// Answer of [lookup()].
+pub(crate) enum Lookup<T> {
+    Found(T),
+    /// `404` declared as JSON: the API found nothing.
+    NotFound,
+    /// `404` not declared as JSON (e.g. the plain text page of Go's `net/http`):
+    /// the server has no such route.
+    NoRoute,
+}
+
+/// Same as [request()], but a `404 Not Found` is an answer rather than a failure.
+pub(crate) async fn lookup<T>(
+    call: Call<'_>,
+    method: Method,
+    uri: &Url,
+    request_body: Vec<u8>,
+) -> Result<Lookup<T>>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    send_json(call, method, uri, request_body, |method, uri, response| {
+        if response.status != StatusCode::NOT_FOUND {
+            return parse_response(method, uri, response).map(Lookup::Found);
+        }
+        if is_json(&response.headers) {
+            Ok(Lookup::NotFound)
+        } else {
+            Ok(Lookup::NoRoute)
+        }
+    })
+    .await
+}
+
+/// Send a JSON request through `call.transport` and `parse` its response,
+/// recording metrics and tracing of the whole call.
+/// Request and response bodies are never traced: they carry signatures and post contents.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        name = "nextid.http",
+        skip_all,
+        fields(
+            service = %call.operation.service,
+            operation = call.operation.name,
+            method = %method,
+            host = uri.host_str(),
+            path = uri.path(),
+            status,
+            latency_ms,
+            backend
         )
-        .await?;
+    )
+)]
+async fn send_json<R>(
+    call: Call<'_>,
+    method: Method,
+    uri: &Url,
+    request_body: Vec<u8>,
+    parse: impl FnOnce(Method, &Url, &TransportResponse) -> Result<R>,
+) -> Result<R> {
+    let mut headers = HeaderMap::new();
+    headers.insert("Accept", HeaderValue::from_static("application/json"));
+    headers.insert("Content-Type", HeaderValue::from_static("application/json"));
+    headers.insert(
+        "User-Agent",
+        HeaderValue::from_static("NextID-SDK-Rust/0.1.0"),
+    );
+    let started = Instant::now();
+    let request_bytes = request_body.len();
+    let mut transport_request = TransportRequest::new(method.clone(), uri.clone(), call.operation);
+    transport_request.headers = headers;
+    transport_request.body = request_body;
+    let record = transport_request.record.clone();
+    let sending = call.transport.send(transport_request);
+    let response = match call.timeout {
+        Some(timeout) => tokio::time::timeout(timeout, sending)
+            .await
+            .unwrap_or(Err(Error::Timeout(timeout))),
+        None => sending.await,
+    };
+    let (status, response_bytes) = match &response {
+        Ok(response) => (Some(response.status), response.body.len()),
+        Err(_) => (None, 0),
+    };
+    let backend = record.backend();
+    let result = response.and_then(|response| parse(method, uri, &response));
+
+    #[cfg(feature = "tracing")]
+    {
+        let span = tracing::Span::current();
+        span.record("latency_ms", started.elapsed().as_millis() as u64);
+        if let Some(status) = status {
+            span.record("status", status.as_u16());
+        }
+        if let Some(backend) = &backend {
+            span.record("backend", backend.as_str());
+        }
+        if let Err(err) = &result {
+            tracing::warn!(error = %err, "request failed");
+        }
+    }
+    if let Some(metrics) = call.metrics {
+        metrics.record(&RequestMetrics {
+            operation: call.operation,
+            status,
+            error: result.as_ref().err().map(Error::label),
+            latency: started.elapsed(),
+            request_bytes,
+            response_bytes,
+            backend,
+        });
+    }
+    result
+}
+
+/// Turn non-2xx responses into [Error::ServerError], and parse the others.
+fn parse_response<T>(method: Method, uri: &Url, response: &TransportResponse) -> Result<T>
+where
+    T: for<'de> Deserialize<'de>,
+{
     if [StatusCode::OK, StatusCode::CREATED]
         .into_iter()
-        .all(|status| status != response.status())
+        .all(|status| status != response.status)
     {
-        // TODO: Change this `println` into `error!()` logger here.
-        let body: ErrorResponse = parse_body(&mut response).await?;
-        return Err(Error::ServerError(format!(
-            "Status: {}, Error: {}",
-            response.status(),
-            body.message
-        )));
+        let body = String::from_utf8_lossy(&response.body).into_owned();
+        let message = error_message(response.status, &body);
+        return Err(Error::ServerError(Box::new(ServerError {
+            status: response.status,
+            kind: error_kind(&message),
+            message,
+            body,
+            method,
+            path: uri.path().to_string(),
+        })));
     }
 
-    parse_body(&mut response).await
-}
-
-fn new_client() -> Client<HttpsConnector<HttpConnector>> {
-    let https = HttpsConnector::new();
-    Client::builder().build::<_, Body>(https)
+    parse_body(response)
 }
 
-async fn parse_body<T>(resp: &mut Response<Body>) -> Result<T>
+fn parse_body<T>(resp: &TransportResponse) -> Result<T>
 where
     T: for<'de> Deserialize<'de>,
 {
-    let mut body_bytes: Vec<u8> = vec![];
-    while let Some(chunk) = resp.body_mut().data().await {
-        let mut chunk_bytes = chunk.unwrap().to_vec();
-        body_bytes.append(&mut chunk_bytes);
+    if let Some(content_type) = resp.headers.get(CONTENT_TYPE) {
+        if !is_json(&resp.headers) {
+            let content_type = String::from_utf8_lossy(content_type.as_bytes());
+            return Err(Error::UnexpectedContentType(content_type.into_owned()));
+        }
     }
-    let body = std::str::from_utf8(&body_bytes).unwrap();
+    Ok(serde_json::from_slice(&resp.body)?)
+}
+
+/// Whether `headers` declare a JSON body.
+fn is_json(headers: &HeaderMap) -> bool {
+    headers.get(CONTENT_TYPE).is_some_and(|content_type| {
+        String::from_utf8_lossy(content_type.as_bytes()).contains("json")
+    })
+}
 
-    Ok(serde_json::from_str(body)?)
+/// Extract `message` from an error response body.
+/// Bodies which are not an [ErrorResponse] (e.g. an HTML page from a proxy)
+/// are kept as-is (trimmed), or replaced by the status reason if empty.
+fn error_message(status: StatusCode, body: &str) -> String {
+    if let Ok(ErrorResponse { message }) = serde_json::from_str(body) {
+        return message;
+    }
+    let trimmed = body.trim();
+    if trimmed.is_empty() {
+        return status.canonical_reason().unwrap_or_default().to_string();
+    }
+    trimmed.chars().take(200).collect()
+}
+
+/// `ErrorResponse.message` of ProofService when the sign payload (`uuid`) is unknown or expired.
+pub(crate) const PAYLOAD_EXPIRED: &str = "payload not found or expired";
+/// `ErrorResponse.message` of both services when a signature cannot be decoded.
+pub(crate) const SIGNATURE_INVALID: &str = "signature invalid";
+/// `ErrorResponse.message` of both services when a signature does not recover to the avatar.
+pub(crate) const SIGNATURE_MISMATCH: &str = "signature mismatch";
+/// `ErrorResponse.message` of ProofService when an `ethereum` wallet signature does not match.
+pub(crate) const WALLET_SIGNATURE_MISMATCH: &str = "wallet signature mismatch";
+/// `ErrorResponse.message` of ProofService when the proof post cannot be fetched.
+pub(crate) const PROOF_POST_NOT_FOUND: &str = "proof post not found";
+/// `ErrorResponse.message` of ProofService when creating a binding twice.
+pub(crate) const BINDING_EXISTS: &str = "this binding already exists";
+/// `ErrorResponse.message` of both services for an avatar not bound to the identity.
+pub(crate) const BINDING_NOT_FOUND: &str = "binding not found";
+
+/// Messages of the ProofService (`nextdotid/proof_server`) and KVService (`nextdotid/kv_server`)
+/// error responses, and what they mean.
+const KNOWN_ERRORS: [(&str, ServerErrorKind); 7] = [
+    (PAYLOAD_EXPIRED, ServerErrorKind::PayloadExpired),
+    (SIGNATURE_INVALID, ServerErrorKind::SignatureMismatch),
+    (SIGNATURE_MISMATCH, ServerErrorKind::SignatureMismatch),
+    (
+        WALLET_SIGNATURE_MISMATCH,
+        ServerErrorKind::SignatureMismatch,
+    ),
+    (PROOF_POST_NOT_FOUND, ServerErrorKind::ProofPostNotFound),
+    (BINDING_EXISTS, ServerErrorKind::AlreadyBound),
+    (BINDING_NOT_FOUND, ServerErrorKind::BindingNotFound),
+];
+
+/// Recognize [KNOWN_ERRORS] in `ErrorResponse.message`, possibly followed by `: <details>`.
+pub(crate) fn error_kind(message: &str) -> ServerErrorKind {
+    KNOWN_ERRORS
+        .into_iter()
+        .find(|(known, _)| {
+            message
+                .strip_prefix(known)
+                .is_some_and(|details| details.is_empty() || details.starts_with(": "))
+        })
+        .map(|(_, kind)| kind)
+        .unwrap_or_else(|| ServerErrorKind::Unknown(message.to_string()))
 }
@@ -0,0 +1,99 @@
+use http::StatusCode;
+use std::time::Duration;
+use strum_macros::Display;
+
+/// NextID service an [Operation] talks to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Display)]
+pub enum Service {
+    #[strum(serialize = "proof_service")]
+    ProofService,
+    #[strum(serialize = "kv_service")]
+    KVService,
+}
+
+/// SDK operation an HTTP request belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Operation {
+    pub service: Service,
+    /// Endpoint variant: `production`, `staging` or `custom`.
+    pub endpoint: &'static str,
+    /// Operation name, e.g. `find_by`, `find_by_avatar`, `payload` or `submit`.
+    pub name: &'static str,
+}
+
+/// Measurements of a single API call, handed to a [MetricsRecorder].
+#[derive(Debug, Clone)]
+pub struct RequestMetrics {
+    pub operation: Operation,
+    /// Response status. `None` if no response arrived at all.
+    pub status: Option<StatusCode>,
+    /// Short error label (e.g. `timeout`, `server_error`). `None` if the call succeeded.
+    pub error: Option<&'static str>,
+    /// Time spent on the whole call, retries included.
+    pub latency: Duration,
+    pub request_bytes: usize,
+    pub response_bytes: usize,
+    /// Base URL that served the response, when sent through a
+    /// [FailoverTransport](crate::util::failover::FailoverTransport).
+    pub backend: Option<String>,
+}
+
+/// Receives [RequestMetrics] of every API call made by a client.
+///
+/// # Examples
+/// ```rust
+/// # use nextid_sdk::util::metrics::{MetricsRecorder, RequestMetrics};
+/// struct PrintRecorder;
+///
+/// impl MetricsRecorder for PrintRecorder {
+///     fn record(&self, metrics: &RequestMetrics) {
+///         println!("{} {} took {:?}", metrics.operation.service, metrics.operation.name, metrics.latency);
+///     }
+/// }
+/// ```
+pub trait MetricsRecorder: Send + Sync {
+    fn record(&self, metrics: &RequestMetrics);
+}
+
+/// [MetricsRecorder] emitting through the [`metrics`](https://docs.rs/metrics) facade.
+///
+/// | Metric                                | Type      | Extra labels |
+/// |---------------------------------------|-----------|--------------|
+/// | `nextid_requests_total`               | counter   | `status`     |
+/// | `nextid_request_errors_total`         | counter   | `error`      |
+/// | `nextid_request_duration_seconds`     | histogram |              |
+/// | `nextid_request_bytes`                | histogram |              |
+/// | `nextid_response_bytes`               | histogram |              |
+///
+/// All of them are labelled by `service`, `endpoint` and `operation`.
+#[cfg(feature = "metrics")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MetricsFacade;
+
+#[cfg(feature = "metrics")]
+impl MetricsRecorder for MetricsFacade {
+    fn record(&self, m: &RequestMetrics) {
+        let labels = [
+            ("service", m.operation.service.to_string()),
+            ("endpoint", m.operation.endpoint.to_string()),
+            ("operation", m.operation.name.to_string()),
+        ];
+        let status = m
+            .status
+            .map(|status| status.as_u16().to_string())
+            .unwrap_or_else(|| "none".to_string());
+
+        let mut with_status = labels.to_vec();
+        with_status.push(("status", status));
+        metrics::counter!("nextid_requests_total", &with_status).increment(1);
+        if let Some(error) = m.error {
+            let mut with_error = labels.to_vec();
+            with_error.push(("error", error.to_string()));
+            metrics::counter!("nextid_request_errors_total", &with_error).increment(1);
+        }
+        metrics::histogram!("nextid_request_duration_seconds", &labels)
+            .record(m.latency.as_secs_f64());
+        metrics::histogram!("nextid_request_bytes", &labels).record(m.request_bytes as f64);
+        metrics::histogram!("nextid_response_bytes", &labels).record(m.response_bytes as f64);
+    }
+}
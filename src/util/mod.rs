@@ -1,12 +1,26 @@
+/// In-memory cache of query responses
+pub mod cache;
+/// Recording and replaying HTTP interactions for offline tests
+pub mod cassette;
 /// Crypto-related helper functions
 pub mod crypto;
+/// Failing over between several backends of one endpoint
+pub mod failover;
 /// HTTP-related helper functions
-pub(crate) mod http;
+pub mod http;
+/// Labels and recorders of per-request metrics
+pub mod metrics;
+/// Client-side rate limiting with token buckets
+pub mod rate_limit;
+/// Retrying failed requests with exponential backoff
+pub mod retry;
+/// Sharing one in-flight call among identical concurrent requests
+pub mod singleflight;
 #[cfg(test)]
 mod tests;
 
-use crate::types::Result;
-use chrono::NaiveDateTime;
+use crate::types::{Error, Result};
+use chrono::{DateTime, NaiveDateTime};
 use libsecp256k1::PublicKey;
 use sha3::{Digest, Keccak256};
 
@@ -34,11 +48,7 @@ where
 /// assert_eq!(expected, hex_decode(hexstring).unwrap());
 /// ```
 pub fn hex_decode(hexstring: &str) -> Result<Vec<u8>> {
-    let hex: &str = if hexstring.starts_with("0x") {
-        &hexstring[2..]
-    } else {
-        hexstring
-    };
+    let hex: &str = hexstring.strip_prefix("0x").unwrap_or(hexstring);
 
     hex::decode(hex).map_err(|e| e.into())
 }
@@ -75,10 +85,10 @@ pub fn base64_decode(base64_string: &str) -> Result<Vec<u8>> {
 /// Keccak256(message)
 /// # Examples
 /// ```rust
-/// # use nextid_sdk::crypto::hash_keccak256;
+/// # use nextid_sdk::util::keccak256_hash;
 /// # use hex_literal::hex;
 /// #
-/// let result = hash_keccak256(&"Test123");
+/// let result = keccak256_hash(&"Test123");
 /// let expected: [u8; 32] = hex!("504AF7475B7341893F803C8EBABFBAEA60EAE7B6A42CB006960C3FDB14DCF8AD");
 /// assert_eq!(result, expected);
 /// ```
@@ -93,17 +103,28 @@ pub fn keccak256_hash(message: impl AsRef<[u8]>) -> [u8; 32] {
 /// # Examples
 /// ```rust
 /// # use nextid_sdk::util::ts_string_to_naive;
-/// let naive_dt = ts_string_to_naive("1662708890");
+/// # use chrono::Datelike;
+/// let naive_dt = ts_string_to_naive("1662708890").unwrap();
 /// # assert_eq!(2022, naive_dt.year());
 /// ```
 pub fn ts_string_to_naive(timestamp: &str) -> Result<NaiveDateTime> {
     let timestamp: i64 = timestamp.parse()?;
-    Ok(ts_to_naive(timestamp, 0))
+    try_ts_to_naive(timestamp, 0)
 }
 
 /// Convert timestamp into NaiveDateTime struct.
+/// # Panics
+/// If the timestamp cannot be represented; see [try_ts_to_naive] for a fallible version.
 pub fn ts_to_naive(seconds: i64, ms: u32) -> NaiveDateTime {
-    NaiveDateTime::from_timestamp(seconds, ms * 1000000)
+    try_ts_to_naive(seconds, ms).expect("Timestamp out of range")
+}
+
+/// Same as [ts_to_naive], but fails with [Error::TimestampOutOfRange] instead of panicking.
+pub fn try_ts_to_naive(seconds: i64, ms: u32) -> Result<NaiveDateTime> {
+    ms.checked_mul(1_000_000)
+        .and_then(|nanos| DateTime::from_timestamp(seconds, nanos))
+        .map(|datetime| datetime.naive_utc())
+        .ok_or(Error::TimestampOutOfRange(seconds))
 }
 
 /// Generate Ethereum address from a secp256k1 public key.
@@ -0,0 +1,169 @@
+use crate::{
+    types::Result,
+    util::http::{canonical_base_url, relative_to, Transport, TransportRequest, TransportResponse},
+};
+use async_trait::async_trait;
+use std::{sync::Mutex, time::Duration};
+use tokio::time::Instant;
+
+/// Token bucket settings: `requests_per_second` sustained, up to `burst` at once.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    pub requests_per_second: f64,
+    pub burst: u32,
+}
+
+impl RateLimit {
+    /// # Panics
+    /// If `requests_per_second` is not positive or `burst` is zero.
+    /// # Examples
+    /// ```rust
+    /// # use nextid_sdk::util::rate_limit::RateLimit;
+    /// // 5 requests per second, 10 at once after being idle.
+    /// let limit = RateLimit::new(5.0, 10);
+    /// ```
+    pub fn new(requests_per_second: f64, burst: u32) -> Self {
+        assert!(
+            requests_per_second > 0.0,
+            "RateLimit.requests_per_second must be positive"
+        );
+        assert!(burst > 0, "RateLimit.burst must be positive");
+        Self {
+            requests_per_second,
+            burst,
+        }
+    }
+}
+
+/// [RateLimit] of each endpoint, by base URL. Endpoints without limit are left unlimited.
+/// # Examples
+/// ```rust
+/// # use nextid_sdk::{proof_service::Endpoint, util::rate_limit::{RateLimit, RateLimits}};
+/// let limits = RateLimits::default()
+///     .with(&Endpoint::Production.base_url(), RateLimit::new(5.0, 10))
+///     .with("https://proof-service.example.com", RateLimit::new(50.0, 100));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct RateLimits {
+    /// `(base URL, limit)` pairs, as given by `Endpoint::base_url()`.
+    pub endpoints: Vec<(String, RateLimit)>,
+}
+
+impl RateLimits {
+    /// Limit requests to the server rooted at `base_url`, replacing any previous limit of it.
+    pub fn with(mut self, base_url: &str, limit: RateLimit) -> Self {
+        let base_url = canonical_base_url(base_url);
+        self.endpoints
+            .retain(|(known, _)| canonical_base_url(known) != base_url);
+        self.endpoints.push((base_url, limit));
+        self
+    }
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    limit: RateLimit,
+    /// Tokens left at `updated_at`. Negative when callers are queued.
+    state: Mutex<(f64, Instant)>,
+}
+
+impl TokenBucket {
+    fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            state: Mutex::new((limit.burst as f64, Instant::now())),
+        }
+    }
+
+    /// Take a token, waiting for the bucket to refill if needed.
+    /// Tokens are reserved in call order, so queued callers are served first come, first served.
+    /// A caller dropped while waiting gives its token back.
+    async fn acquire(&self) {
+        let wait = {
+            let mut state = self.state.lock().unwrap();
+            let (tokens, updated_at) = *state;
+            let now = Instant::now();
+            let refilled = (tokens
+                + now.duration_since(updated_at).as_secs_f64() * self.limit.requests_per_second)
+                .min(self.limit.burst as f64);
+            *state = (refilled - 1.0, now);
+            if refilled >= 1.0 {
+                Duration::ZERO
+            } else {
+                Duration::from_secs_f64((1.0 - refilled) / self.limit.requests_per_second)
+            }
+        };
+        if !wait.is_zero() {
+            #[cfg(feature = "tracing")]
+            tracing::debug!(wait_ms = wait.as_millis() as u64, "rate limited");
+            let reservation = Reservation(self);
+            tokio::time::sleep(wait).await;
+            std::mem::forget(reservation);
+        }
+    }
+
+    fn refund(&self) {
+        self.state.lock().unwrap().0 += 1.0;
+    }
+}
+
+/// Token reserved by a caller still waiting for it.
+struct Reservation<'a>(&'a TokenBucket);
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        self.0.refund();
+    }
+}
+
+/// [Transport] holding back requests of `inner` to stay within [RateLimits].
+/// Each endpoint has its own bucket, so e.g. a self-hosted server is not held back by
+/// the limit of `Endpoint::Production`. Buckets live inside the transport, so every client
+/// sharing it (e.g. clones of a [NextIdClient](crate::client::NextIdClient)) shares the limits.
+/// Callers over the limit wait for their turn instead of failing.
+///
+/// # Examples
+/// ```rust
+/// # use std::sync::Arc;
+/// # use nextid_sdk::proof_service::Endpoint;
+/// # use nextid_sdk::util::{http::HyperTransport, rate_limit::{RateLimit, RateLimits, RateLimitTransport}};
+/// let transport = RateLimitTransport::new(
+///     HyperTransport::new(),
+///     RateLimits::default().with(&Endpoint::Production.base_url(), RateLimit::new(5.0, 10)),
+/// );
+/// let client = Endpoint::Production.with_transport(Arc::new(transport));
+/// ```
+pub struct RateLimitTransport<T> {
+    inner: T,
+    /// Buckets by canonical base URL.
+    buckets: Vec<(String, TokenBucket)>,
+}
+
+impl<T> RateLimitTransport<T> {
+    pub fn new(inner: T, limits: RateLimits) -> Self {
+        Self {
+            inner,
+            buckets: limits
+                .endpoints
+                .into_iter()
+                .map(|(base_url, limit)| (canonical_base_url(&base_url), TokenBucket::new(limit)))
+                .collect(),
+        }
+    }
+}
+
+#[async_trait]
+impl<T: Transport> Transport for RateLimitTransport<T> {
+    async fn send(&self, request: TransportRequest) -> Result<TransportResponse> {
+        // The most specific base URL wins, e.g. `https://host/api` over `https://host`.
+        let bucket = self
+            .buckets
+            .iter()
+            .filter(|(base_url, _)| relative_to(&request.url, base_url).is_some())
+            .max_by_key(|(base_url, _)| base_url.len());
+        if let Some((_, bucket)) = bucket {
+            bucket.acquire().await;
+        }
+        self.inner.send(request).await
+    }
+}
@@ -0,0 +1,177 @@
+use crate::{
+    types::{Error, Result},
+    util::http::{Transport, TransportRequest, TransportResponse},
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use http::{header::RETRY_AFTER, HeaderMap, StatusCode};
+use rand::Rng;
+use std::time::Duration;
+
+/// Which responses are worth another attempt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetryOn {
+    /// Any `5xx` response.
+    ServerErrors,
+    /// `429 Too Many Requests`
+    TooManyRequests,
+    /// `408 Request Timeout`
+    RequestTimeout,
+    /// A specific status code.
+    Status(StatusCode),
+}
+
+impl RetryOn {
+    fn matches(&self, status: StatusCode) -> bool {
+        match self {
+            RetryOn::ServerErrors => status.is_server_error(),
+            RetryOn::TooManyRequests => status == StatusCode::TOO_MANY_REQUESTS,
+            RetryOn::RequestTimeout => status == StatusCode::REQUEST_TIMEOUT,
+            RetryOn::Status(expected) => status == *expected,
+        }
+    }
+}
+
+/// Retry policy with exponential backoff.
+/// Only idempotent requests (`GET` queries) are retried unless
+/// `retry_non_idempotent` is set.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Max attempts in total, including the first one. `1` disables retrying.
+    pub max_attempts: u32,
+    /// Backoff before the second attempt.
+    pub initial_backoff: Duration,
+    /// Upper bound of a single backoff.
+    /// A `Retry-After` longer than this stops retrying.
+    pub max_backoff: Duration,
+    /// Backoff grows by this factor after each attempt.
+    pub multiplier: f64,
+    /// Randomize each backoff between zero and its computed value ("full jitter").
+    pub jitter: bool,
+    /// Responses worth another attempt. Network errors and connect timeouts are always retried.
+    pub retry_on: Vec<RetryOn>,
+    /// Also retry non-idempotent requests (e.g. `ProofProcedure::submit`).
+    pub retry_non_idempotent: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(10),
+            multiplier: 2.0,
+            jitter: true,
+            retry_on: vec![
+                RetryOn::ServerErrors,
+                RetryOn::TooManyRequests,
+                RetryOn::RequestTimeout,
+            ],
+            retry_non_idempotent: false,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Never retry.
+    pub fn disabled() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Default::default()
+        }
+    }
+
+    /// Backoff before attempt `attempt + 1` (`attempt` starts from `1`), without jitter.
+    /// # Examples
+    /// ```rust
+    /// # use nextid_sdk::util::retry::RetryPolicy;
+    /// # use std::time::Duration;
+    /// let policy = RetryPolicy::default();
+    /// assert_eq!(Duration::from_millis(200), policy.backoff(1));
+    /// assert_eq!(Duration::from_millis(400), policy.backoff(2));
+    /// ```
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = self.multiplier.powi(attempt.saturating_sub(1) as i32);
+        self.initial_backoff.mul_f64(factor).min(self.max_backoff)
+    }
+
+    fn should_retry_status(&self, status: StatusCode) -> bool {
+        self.retry_on
+            .iter()
+            .any(|retry_on| retry_on.matches(status))
+    }
+
+    fn jittered(&self, backoff: Duration) -> Duration {
+        if !self.jitter || backoff.is_zero() {
+            return backoff;
+        }
+        backoff.mul_f64(rand::thread_rng().gen_range(0.0..=1.0))
+    }
+}
+
+/// Parse `Retry-After` header (delay-seconds or HTTP-date).
+pub(crate) fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    Some(
+        (date.with_timezone(&Utc) - Utc::now())
+            .to_std()
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+/// [Transport] retrying failed requests of `inner` according to a [RetryPolicy].
+pub struct RetryTransport<T> {
+    inner: T,
+    policy: RetryPolicy,
+}
+
+impl<T> RetryTransport<T> {
+    pub fn new(inner: T, policy: RetryPolicy) -> Self {
+        Self { inner, policy }
+    }
+}
+
+#[async_trait]
+impl<T: Transport> Transport for RetryTransport<T> {
+    async fn send(&self, request: TransportRequest) -> Result<TransportResponse> {
+        let retryable = request.method.is_idempotent() || self.policy.retry_non_idempotent;
+        let max_attempts = if retryable {
+            self.policy.max_attempts.max(1)
+        } else {
+            1
+        };
+
+        let mut attempt: u32 = 1;
+        loop {
+            let sending = self.inner.send(request.clone());
+            #[cfg(feature = "tracing")]
+            let sending =
+                tracing::Instrument::instrument(sending, tracing::debug_span!("attempt", attempt));
+            let result = sending.await;
+            if attempt >= max_attempts {
+                return result;
+            }
+            let backoff = match &result {
+                Ok(response) if self.policy.should_retry_status(response.status) => {
+                    match retry_after(&response.headers) {
+                        Some(wait) if wait > self.policy.max_backoff => return result,
+                        Some(wait) => wait,
+                        None => self.policy.jittered(self.policy.backoff(attempt)),
+                    }
+                }
+                Err(Error::HttpError(_) | Error::Timeout(_)) => {
+                    self.policy.jittered(self.policy.backoff(attempt))
+                }
+                _ => return result,
+            };
+            #[cfg(feature = "tracing")]
+            tracing::debug!(attempt, backoff_ms = backoff.as_millis() as u64, "retrying");
+            tokio::time::sleep(backoff).await;
+            attempt += 1;
+        }
+    }
+}
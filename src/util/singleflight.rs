@@ -0,0 +1,120 @@
+use crate::{
+    types::Result,
+    util::http::{Transport, TransportRequest, TransportResponse},
+};
+use async_trait::async_trait;
+use http::Method;
+use std::{collections::HashMap, sync::Mutex};
+use tokio::sync::oneshot;
+
+/// Response of the shared call, with the backend that served it.
+type Shared = (TransportResponse, Option<String>);
+type Waiters = HashMap<String, Vec<oneshot::Sender<Shared>>>;
+
+/// [Transport] letting concurrent identical `GET` requests share one in-flight call of `inner`.
+///
+/// The first caller sends the request; callers asking for the same URL meanwhile
+/// wait for it and get a copy of its response.
+/// If that call fails with an [Error](crate::types::Error) or is cancelled,
+/// waiting callers send their own request instead.
+///
+/// # Examples
+/// ```rust
+/// # use std::sync::Arc;
+/// # use nextid_sdk::proof_service::Endpoint;
+/// # use nextid_sdk::util::{http::default_transport, singleflight::SingleflightTransport};
+/// let transport = SingleflightTransport::new(default_transport());
+/// let client = Endpoint::Production.with_transport(Arc::new(transport));
+/// ```
+pub struct SingleflightTransport<T> {
+    inner: T,
+    in_flight: Mutex<Waiters>,
+}
+
+impl<T> SingleflightTransport<T> {
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            in_flight: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+/// Marks the call of `key` as in flight until finished or dropped.
+struct Leader<'a> {
+    in_flight: &'a Mutex<Waiters>,
+    key: Option<String>,
+}
+
+impl Leader<'_> {
+    /// Stop accepting waiters and return those gathered so far.
+    fn finish(mut self) -> Vec<oneshot::Sender<Shared>> {
+        let key = self.key.take().unwrap();
+        self.in_flight
+            .lock()
+            .unwrap()
+            .remove(&key)
+            .unwrap_or_default()
+    }
+}
+
+impl Drop for Leader<'_> {
+    fn drop(&mut self) {
+        // Cancelled: dropping the senders wakes waiters up.
+        if let Some(key) = self.key.take() {
+            self.in_flight.lock().unwrap().remove(&key);
+        }
+    }
+}
+
+#[async_trait]
+impl<T: Transport> Transport for SingleflightTransport<T> {
+    async fn send(&self, request: TransportRequest) -> Result<TransportResponse> {
+        if request.method != Method::GET {
+            return self.inner.send(request).await;
+        }
+
+        let key = request.url.to_string();
+        let waiting = {
+            let mut in_flight = self.in_flight.lock().unwrap();
+            match in_flight.get_mut(&key) {
+                Some(waiters) => {
+                    let (sender, receiver) = oneshot::channel();
+                    waiters.push(sender);
+                    Some(receiver)
+                }
+                None => {
+                    in_flight.insert(key.clone(), vec![]);
+                    None
+                }
+            }
+        };
+        if let Some(receiver) = waiting {
+            #[cfg(feature = "tracing")]
+            tracing::debug!("joined in-flight request");
+            return match receiver.await {
+                Ok((response, backend)) => {
+                    if let Some(backend) = backend {
+                        request.record.set_backend(backend);
+                    }
+                    Ok(response)
+                }
+                Err(_) => self.inner.send(request).await,
+            };
+        }
+
+        let leader = Leader {
+            in_flight: &self.in_flight,
+            key: Some(key),
+        };
+        let record = request.record.clone();
+        let result = self.inner.send(request).await;
+        let waiters = leader.finish();
+        if let Ok(response) = &result {
+            for waiter in waiters {
+                let _ = waiter.send((response.clone(), record.backend()));
+            }
+        }
+        result
+    }
+}
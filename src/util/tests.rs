@@ -1,7 +1,839 @@
+use super::cache::{CacheConfig, CacheTransport};
+use super::cassette::{Cassette, RecordingTransport, ReplayTransport};
+use super::crypto::Secp256k1KeyPair;
+use super::failover::{FailoverPolicy, FailoverTransport, Selection};
+use super::http::{
+    error_kind, read_body, request, Call, Transport, TransportRequest, TransportResponse,
+    BINDING_EXISTS, PAYLOAD_EXPIRED,
+};
+use super::metrics::{MetricsRecorder, Operation, RequestMetrics, Service};
+use super::rate_limit::{RateLimit, RateLimitTransport, RateLimits};
+use super::retry::{retry_after, RetryPolicy, RetryTransport};
+use super::singleflight::SingleflightTransport;
 use super::*;
-use crate::types::{Error, Result};
+use crate::types::{Error, Result, ServerErrorKind};
+use ::http::{HeaderMap, HeaderValue, Method, StatusCode};
+use serde::Deserialize;
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// In-memory transport replying with queued responses.
+/// The last one is repeated once the queue runs out.
+struct FakeTransport {
+    responses: Mutex<VecDeque<TransportResponse>>,
+    requests: Mutex<Vec<TransportRequest>>,
+}
+
+impl FakeTransport {
+    fn new(status: StatusCode, body: &'static str) -> Self {
+        Self::sequence(vec![(status, body)])
+    }
+
+    fn sequence(responses: Vec<(StatusCode, &'static str)>) -> Self {
+        Self {
+            responses: Mutex::new(
+                responses
+                    .into_iter()
+                    .map(|(status, body)| TransportResponse {
+                        status,
+                        headers: HeaderMap::new(),
+                        body: body.as_bytes().to_vec(),
+                    })
+                    .collect(),
+            ),
+            requests: Mutex::new(vec![]),
+        }
+    }
+
+    fn with_header(self, name: &'static str, value: &'static str) -> Self {
+        for response in self.responses.lock().unwrap().iter_mut() {
+            response
+                .headers
+                .insert(name, HeaderValue::from_static(value));
+        }
+        self
+    }
+
+    fn request_count(&self) -> usize {
+        self.requests.lock().unwrap().len()
+    }
+}
+
+#[async_trait::async_trait]
+impl Transport for FakeTransport {
+    async fn send(&self, request: TransportRequest) -> Result<TransportResponse> {
+        self.requests.lock().unwrap().push(request);
+        let mut responses = self.responses.lock().unwrap();
+        if responses.len() > 1 {
+            Ok(responses.pop_front().unwrap())
+        } else {
+            Ok(responses.front().cloned().unwrap())
+        }
+    }
+}
+
+const OPERATION: Operation = Operation {
+    service: Service::ProofService,
+    endpoint: "custom",
+    name: "test",
+};
+
+fn call(transport: &dyn Transport, timeout: Option<Duration>) -> Call<'_> {
+    Call {
+        transport,
+        operation: OPERATION,
+        timeout,
+        metrics: None,
+    }
+}
+
+#[derive(Deserialize)]
+struct Hello {
+    hello: String,
+}
 
 #[tokio::test]
 async fn test_something() -> Result<()> {
     Ok(())
 }
+
+#[test]
+fn test_timestamp_out_of_range() -> Result<()> {
+    assert_eq!(
+        1662708890,
+        ts_string_to_naive("1662708890")?.and_utc().timestamp()
+    );
+    assert!(matches!(
+        ts_string_to_naive(&i64::MAX.to_string()),
+        Err(Error::TimestampOutOfRange(i64::MAX))
+    ));
+    assert!(try_ts_to_naive(0, u32::MAX).is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_request_through_transport() -> Result<()> {
+    let transport = FakeTransport::new(StatusCode::OK, r#"{"hello": "world"}"#);
+    let url = url::Url::parse("https://example.com/healthz")?;
+    let response: Hello = request(call(&transport, None), Method::GET, &url, vec![]).await?;
+    assert_eq!("world", response.hello);
+
+    let requests = transport.requests.lock().unwrap();
+    assert_eq!(1, requests.len());
+    assert_eq!(Method::GET, requests[0].method);
+    assert_eq!(url, requests[0].url);
+    assert_eq!("application/json", requests[0].headers["Accept"]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_request_server_error() -> Result<()> {
+    let transport = FakeTransport::new(StatusCode::NOT_FOUND, r#"{"message": "not found"}"#);
+    let url = url::Url::parse("https://example.com/v1/proof")?;
+    let result: Result<Hello> = request(call(&transport, None), Method::GET, &url, vec![]).await;
+    let err = result.err().expect("ServerError expected");
+    assert!(err.is_not_found());
+    assert!(err.is_client_error());
+    assert!(!err.is_retryable());
+    let server_error = err.server_error().unwrap();
+    assert_eq!("not found", server_error.message);
+    assert_eq!(Method::GET, server_error.method);
+    assert_eq!("/v1/proof", server_error.path);
+    assert_eq!(r#"{"message": "not found"}"#, server_error.body);
+    Ok(())
+}
+
+#[test]
+fn test_hex_decode_prefix() -> Result<()> {
+    assert_eq!(hex_decode("0x0102")?, hex_decode("0102")?);
+    Ok(())
+}
+
+fn fast_retry() -> RetryPolicy {
+    RetryPolicy {
+        initial_backoff: Duration::from_millis(1),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_retry_get_until_success() -> Result<()> {
+    let transport = RetryTransport::new(
+        FakeTransport::sequence(vec![
+            (StatusCode::BAD_GATEWAY, "<html>Bad gateway</html>"),
+            (StatusCode::SERVICE_UNAVAILABLE, r#"{"message": "busy"}"#),
+            (StatusCode::OK, r#"{"hello": "world"}"#),
+        ]),
+        fast_retry(),
+    );
+    let url = url::Url::parse("https://example.com/healthz")?;
+    let response: Hello = request(call(&transport, None), Method::GET, &url, vec![]).await?;
+    assert_eq!("world", response.hello);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_retry_gives_up_after_max_attempts() -> Result<()> {
+    let fake = Arc::new(FakeTransport::new(
+        StatusCode::SERVICE_UNAVAILABLE,
+        r#"{"message": "busy"}"#,
+    ));
+    let transport = RetryTransport::new(fake.clone(), fast_retry());
+    let url = url::Url::parse("https://example.com/v1/proof")?;
+    let result: Result<Hello> = request(call(&transport, None), Method::GET, &url, vec![]).await;
+    assert!(result.is_err());
+    assert_eq!(3, fake.request_count());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_retry_skips_post_unless_opted_in() -> Result<()> {
+    let url = url::Url::parse("https://example.com/v1/proof")?;
+    let responses = vec![
+        (StatusCode::SERVICE_UNAVAILABLE, r#"{"message": "busy"}"#),
+        (StatusCode::CREATED, r#"{}"#),
+    ];
+
+    let fake = Arc::new(FakeTransport::sequence(responses.clone()));
+    let transport = RetryTransport::new(fake.clone(), fast_retry());
+    let result: Result<serde_json::Value> =
+        request(call(&transport, None), Method::POST, &url, b"{}".to_vec()).await;
+    assert!(result.is_err());
+    assert_eq!(1, fake.request_count());
+
+    let opted_in = RetryPolicy {
+        retry_non_idempotent: true,
+        ..fast_retry()
+    };
+    let transport = RetryTransport::new(FakeTransport::sequence(responses), opted_in);
+    let result: Result<serde_json::Value> =
+        request(call(&transport, None), Method::POST, &url, b"{}".to_vec()).await;
+    assert!(result.is_ok());
+    Ok(())
+}
+
+#[test]
+fn test_retry_after() {
+    let mut headers = HeaderMap::new();
+    assert_eq!(None, retry_after(&headers));
+    headers.insert("Retry-After", HeaderValue::from_static("3"));
+    assert_eq!(Some(Duration::from_secs(3)), retry_after(&headers));
+    headers.insert(
+        "Retry-After",
+        HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"),
+    );
+    assert_eq!(Some(Duration::ZERO), retry_after(&headers));
+}
+
+#[test]
+fn test_retry_backoff_capped() {
+    let policy = RetryPolicy {
+        max_backoff: Duration::from_millis(500),
+        ..Default::default()
+    };
+    assert_eq!(Duration::from_millis(500), policy.backoff(10));
+}
+
+/// Transport which never answers.
+struct HangingTransport;
+
+#[async_trait::async_trait]
+impl Transport for HangingTransport {
+    async fn send(&self, _request: TransportRequest) -> Result<TransportResponse> {
+        std::future::pending().await
+    }
+}
+
+#[tokio::test]
+async fn test_request_timeout() -> Result<()> {
+    let url = url::Url::parse("https://example.com/v1/proof")?;
+    let timeout = Duration::from_millis(10);
+    let result: Result<Hello> = request(
+        call(&HangingTransport, Some(timeout)),
+        Method::GET,
+        &url,
+        vec![],
+    )
+    .await;
+    match result {
+        Err(Error::Timeout(after)) => assert_eq!(timeout, after),
+        _ => panic!("Timeout expected"),
+    }
+    Ok(())
+}
+
+#[test]
+fn test_error_kind() {
+    use ServerErrorKind::*;
+    let cases = [
+        (PAYLOAD_EXPIRED, PayloadExpired),
+        (
+            "signature invalid: no signature in proof post",
+            SignatureMismatch,
+        ),
+        ("signature mismatch", SignatureMismatch),
+        ("wallet signature mismatch", SignatureMismatch),
+        ("proof post not found", ProofPostNotFound),
+        (BINDING_EXISTS, AlreadyBound),
+        ("binding not found", BindingNotFound),
+    ];
+    for (message, expected) in cases {
+        assert_eq!(expected, error_kind(message), "{}", message);
+    }
+    // Look-alikes are not guessed.
+    for message in [
+        "token expired",
+        "message not found",
+        "Signature mismatch",
+        "signature invalidated",
+    ] {
+        assert_eq!(Unknown(message.to_string()), error_kind(message));
+    }
+    assert!(PayloadExpired.requires_new_payload());
+    assert!(!ProofPostNotFound.requires_new_payload());
+}
+
+#[tokio::test]
+async fn test_request_non_json_error_body() -> Result<()> {
+    let transport = FakeTransport::new(
+        StatusCode::BAD_GATEWAY,
+        "<html><body>502 Bad Gateway</body></html>\n",
+    )
+    .with_header("Content-Type", "text/html");
+    let url = url::Url::parse("https://example.com/v1/proof")?;
+    let err = request::<Hello>(call(&transport, None), Method::GET, &url, vec![])
+        .await
+        .err()
+        .expect("ServerError expected");
+    let server_error = err.server_error().expect("ServerError expected");
+    assert_eq!(StatusCode::BAD_GATEWAY, server_error.status);
+    assert_eq!(
+        "<html><body>502 Bad Gateway</body></html>",
+        server_error.message
+    );
+    assert!(err.is_retryable());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_request_unexpected_content_type() -> Result<()> {
+    let transport = FakeTransport::new(StatusCode::OK, "<html></html>")
+        .with_header("Content-Type", "text/html; charset=utf-8");
+    let url = url::Url::parse("https://example.com/v1/proof")?;
+    let result = request::<Hello>(call(&transport, None), Method::GET, &url, vec![]).await;
+    assert!(matches!(result, Err(Error::UnexpectedContentType(_))));
+
+    let transport = FakeTransport::new(StatusCode::OK, r#"{"hello": "world"}"#)
+        .with_header("Content-Type", "application/json; charset=utf-8");
+    let response = request::<Hello>(call(&transport, None), Method::GET, &url, vec![]).await?;
+    assert_eq!("world", response.hello);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_read_body_limit() -> Result<()> {
+    let body = read_body(hyper::Body::from("0123456789"), 10).await?;
+    assert_eq!(b"0123456789".to_vec(), body);
+
+    let result = read_body(hyper::Body::from("0123456789"), 9).await;
+    assert!(matches!(result, Err(Error::BodyTooLarge(9))));
+    Ok(())
+}
+
+#[derive(Default)]
+struct RecordingMetrics(Mutex<Vec<RequestMetrics>>);
+
+impl MetricsRecorder for RecordingMetrics {
+    fn record(&self, metrics: &RequestMetrics) {
+        self.0.lock().unwrap().push(metrics.clone());
+    }
+}
+
+#[tokio::test]
+async fn test_request_metrics() -> Result<()> {
+    let recorder = RecordingMetrics::default();
+    let url = url::Url::parse("https://example.com/healthz")?;
+    let ok = FakeTransport::new(StatusCode::OK, r#"{"hello": "world"}"#);
+    let _: Hello = request(
+        Call {
+            metrics: Some(&recorder),
+            ..call(&ok, None)
+        },
+        Method::POST,
+        &url,
+        b"{}".to_vec(),
+    )
+    .await?;
+    let failing = FakeTransport::new(StatusCode::NOT_FOUND, r#"{"message": "not found"}"#);
+    let _ = request::<Hello>(
+        Call {
+            metrics: Some(&recorder),
+            ..call(&failing, None)
+        },
+        Method::GET,
+        &url,
+        vec![],
+    )
+    .await;
+
+    let recorded = recorder.0.lock().unwrap();
+    assert_eq!(2, recorded.len());
+    assert_eq!(OPERATION, recorded[0].operation);
+    assert_eq!(Some(StatusCode::OK), recorded[0].status);
+    assert_eq!(None, recorded[0].error);
+    assert_eq!(2, recorded[0].request_bytes);
+    assert_eq!(18, recorded[0].response_bytes);
+    assert_eq!(Some(StatusCode::NOT_FOUND), recorded[1].status);
+    assert_eq!(Some("server_error"), recorded[1].error);
+    Ok(())
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_rate_limit_queues_callers() -> Result<()> {
+    let transport = Arc::new(RateLimitTransport::new(
+        FakeTransport::new(StatusCode::OK, r#"{"hello": "world"}"#),
+        RateLimits::default().with("https://example.com/", RateLimit::new(10.0, 2)),
+    ));
+    let url = url::Url::parse("https://example.com/healthz")?;
+    let start = tokio::time::Instant::now();
+    let calls = (0..5).map(|_| {
+        let transport = transport.clone();
+        let url = url.clone();
+        tokio::spawn(async move {
+            request::<Hello>(call(transport.as_ref(), None), Method::GET, &url, vec![]).await
+        })
+    });
+    for handle in calls.collect::<Vec<_>>() {
+        handle.await.unwrap()?;
+    }
+    // 2 at once, then one every 100ms.
+    let elapsed = start.elapsed();
+    assert!(elapsed >= Duration::from_millis(300), "{:?}", elapsed);
+    assert!(elapsed < Duration::from_millis(350), "{:?}", elapsed);
+    Ok(())
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_rate_limit_other_endpoint_unlimited() -> Result<()> {
+    let transport = RateLimitTransport::new(
+        FakeTransport::new(StatusCode::OK, r#"{"hello": "world"}"#),
+        RateLimits::default()
+            .with("https://example.com", RateLimit::new(1.0, 1))
+            .with("https://EXAMPLE.com:443/api/", RateLimit::new(1.0, 3)),
+    );
+    let start = tokio::time::Instant::now();
+    // Same service, another server.
+    let url = url::Url::parse("https://self-hosted.example.com/healthz")?;
+    for _ in 0..3 {
+        request::<Hello>(call(&transport, None), Method::GET, &url, vec![]).await?;
+    }
+    // Its own, more specific bucket.
+    let url = url::Url::parse("https://example.com/api/healthz")?;
+    for _ in 0..3 {
+        request::<Hello>(call(&transport, None), Method::GET, &url, vec![]).await?;
+    }
+    assert_eq!(Duration::ZERO, start.elapsed());
+    Ok(())
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_rate_limit_cancelled_caller_refunds() -> Result<()> {
+    let transport = RateLimitTransport::new(
+        FakeTransport::new(StatusCode::OK, r#"{"hello": "world"}"#),
+        RateLimits::default().with("https://example.com", RateLimit::new(1.0, 1)),
+    );
+    let url = url::Url::parse("https://example.com/healthz")?;
+    let start = tokio::time::Instant::now();
+    request::<Hello>(call(&transport, None), Method::GET, &url, vec![]).await?;
+    // Gives up before its token arrives.
+    let timeout = Some(Duration::from_millis(100));
+    let result = request::<Hello>(call(&transport, timeout), Method::GET, &url, vec![]).await;
+    assert!(matches!(result, Err(Error::Timeout(_))));
+    // Served once the first token is back, not after the abandoned one.
+    request::<Hello>(call(&transport, None), Method::GET, &url, vec![]).await?;
+    assert_eq!(Duration::from_secs(1), start.elapsed());
+    Ok(())
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_cache_hit_until_expired() -> Result<()> {
+    let fake = Arc::new(FakeTransport::new(StatusCode::OK, r#"{"hello": "world"}"#));
+    let transport = CacheTransport::new(
+        fake.clone(),
+        CacheConfig {
+            ttl: Duration::from_secs(60),
+            max_entries: 1,
+        },
+    );
+    let alice = url::Url::parse("https://example.com/v1/proof?platform=twitter&identity=alice")?;
+    let bob = url::Url::parse("https://example.com/v1/proof?platform=twitter&identity=bob")?;
+    for url in [&alice, &alice, &bob, &alice] {
+        request::<Hello>(call(&transport, None), Method::GET, url, vec![]).await?;
+    }
+    // `bob` evicted `alice`.
+    assert_eq!(3, fake.request_count());
+
+    tokio::time::advance(Duration::from_secs(61)).await;
+    request::<Hello>(call(&transport, None), Method::GET, &alice, vec![]).await?;
+    assert_eq!(4, fake.request_count());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cache_invalidated_by_write() -> Result<()> {
+    let fake = Arc::new(FakeTransport::new(StatusCode::OK, r#"{"hello": "world"}"#));
+    let transport = CacheTransport::new(fake.clone(), CacheConfig::default());
+    let avatar = Secp256k1KeyPair::generate(&mut rand::rngs::OsRng);
+    let by_avatar = url::Url::parse_with_params(
+        "https://example.com/v1/kv",
+        &[(
+            "avatar",
+            format!("0x{}", hex_encode(&avatar.pk.serialize_compressed())),
+        )],
+    )?;
+    let alice = url::Url::parse("https://example.com/v1/proof?platform=twitter&identity=alice")?;
+    let bob = url::Url::parse("https://example.com/v1/proof?platform=twitter&identity=bob")?;
+    let batch =
+        url::Url::parse("https://example.com/v1/proof?platform=twitter&identity=carol%2Calice")?;
+    for url in [&by_avatar, &alice, &bob, &batch] {
+        request::<Hello>(call(&transport, None), Method::GET, url, vec![]).await?;
+    }
+    assert_eq!(4, fake.request_count());
+
+    // Uncompressed pubkey and different case still hit the cached entries.
+    let write = serde_json::json!({
+        "platform": "twitter",
+        "identity": "Alice",
+        "public_key": hex_encode(&avatar.pk.serialize()),
+    });
+    let url = url::Url::parse("https://example.com/v1/proof")?;
+    request::<Hello>(
+        call(&transport, None),
+        Method::POST,
+        &url,
+        serde_json::to_vec(&write)?,
+    )
+    .await?;
+    for url in [&by_avatar, &alice, &bob, &batch] {
+        request::<Hello>(call(&transport, None), Method::GET, url, vec![]).await?;
+    }
+    // POST, `by_avatar`, `alice` and `batch` again. `bob` is still cached.
+    assert_eq!(8, fake.request_count());
+    Ok(())
+}
+
+/// Replies like `inner`, after `delay`.
+struct SlowTransport {
+    inner: Arc<FakeTransport>,
+    delay: Duration,
+}
+
+#[async_trait::async_trait]
+impl Transport for SlowTransport {
+    async fn send(&self, request: TransportRequest) -> Result<TransportResponse> {
+        tokio::time::sleep(self.delay).await;
+        self.inner.send(request).await
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_singleflight_shares_in_flight_request() -> Result<()> {
+    let fake = Arc::new(FakeTransport::new(StatusCode::OK, r#"{"hello": "world"}"#));
+    let transport = Arc::new(SingleflightTransport::new(SlowTransport {
+        inner: fake.clone(),
+        delay: Duration::from_millis(100),
+    }));
+    let url = url::Url::parse("https://example.com/v1/proof?platform=twitter&identity=x")?;
+    let calls: Vec<_> = (0..5)
+        .map(|_| {
+            let transport = transport.clone();
+            let url = url.clone();
+            tokio::spawn(async move {
+                request::<Hello>(call(transport.as_ref(), None), Method::GET, &url, vec![]).await
+            })
+        })
+        .collect();
+    for handle in calls {
+        assert_eq!("world", handle.await.unwrap()?.hello);
+    }
+    assert_eq!(1, fake.request_count());
+
+    // Finished calls are not reused.
+    request::<Hello>(call(transport.as_ref(), None), Method::GET, &url, vec![]).await?;
+    assert_eq!(2, fake.request_count());
+    Ok(())
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_singleflight_waiter_survives_cancelled_leader() -> Result<()> {
+    let fake = Arc::new(FakeTransport::new(StatusCode::OK, r#"{"hello": "world"}"#));
+    let transport = Arc::new(SingleflightTransport::new(SlowTransport {
+        inner: fake.clone(),
+        delay: Duration::from_millis(100),
+    }));
+    let url = url::Url::parse("https://example.com/v1/proof")?;
+    let leader = {
+        let transport = transport.clone();
+        let url = url.clone();
+        tokio::spawn(async move {
+            request::<Hello>(call(transport.as_ref(), None), Method::GET, &url, vec![]).await
+        })
+    };
+    tokio::task::yield_now().await;
+    let waiter = {
+        let transport = transport.clone();
+        let url = url.clone();
+        tokio::spawn(async move {
+            request::<Hello>(call(transport.as_ref(), None), Method::GET, &url, vec![]).await
+        })
+    };
+    tokio::task::yield_now().await;
+    leader.abort();
+    assert_eq!("world", waiter.await.unwrap()?.hello);
+    assert_eq!(1, fake.request_count());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_record_then_replay() -> Result<()> {
+    let path = std::env::temp_dir().join(format!("nextid_cassette_{}.json", std::process::id()));
+    let recording = RecordingTransport::new(
+        FakeTransport::new(StatusCode::OK, r#"{"hello": "world"}"#)
+            .with_header("content-type", "application/json"),
+        &path,
+    );
+    let url = url::Url::parse("https://example.com/v1/kv/payload")?;
+    let body = br#"{"avatar": "0x02", "patch": {"a": 1}}"#.to_vec();
+    request::<Hello>(call(&recording, None), Method::POST, &url, body).await?;
+
+    let replay = ReplayTransport::from_file(&path)?;
+    std::fs::remove_file(&path)?;
+    // Different host, same JSON body in another key order.
+    let url = url::Url::parse("http://localhost:8080/v1/kv/payload")?;
+    let body = br#"{"patch": {"a": 1}, "avatar": "0x02"}"#.to_vec();
+    let response = request::<Hello>(call(&replay, None), Method::POST, &url, body.clone()).await?;
+    assert_eq!("world", response.hello);
+    assert_eq!(0, replay.remaining());
+
+    // Each interaction is served once.
+    let again = request::<Hello>(call(&replay, None), Method::POST, &url, body).await;
+    assert!(matches!(again, Err(Error::ReplayMismatch(_))));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_replay_strict_match() -> Result<()> {
+    let cassette: Cassette = serde_json::from_value(serde_json::json!({
+        "interactions": [
+            {
+                "request": {
+                    "method": "GET",
+                    "url": "https://proof-service.nextnext.id/v1/proof?platform=twitter&identity=yeiwb&page=1",
+                },
+                "response": { "status": 200, "body": { "hello": "page 1" } },
+            },
+            {
+                "request": { "method": "GET", "url": "https://proof-service.nextnext.id/healthz" },
+                "response": { "status": 200, "body": { "hello": "healthz" } },
+            },
+        ],
+    }))?;
+    let replay = ReplayTransport::new(cassette);
+    let url = url::Url::parse(
+        "https://proof-service.nextnext.id/v1/proof?platform=twitter&identity=yeiwb&page=2",
+    )?;
+    let result = request::<Hello>(call(&replay, None), Method::GET, &url, vec![]).await;
+    assert!(matches!(result, Err(Error::ReplayMismatch(_))));
+    assert_eq!(2, replay.remaining());
+
+    // Query parameters in another order, against another host.
+    let url =
+        url::Url::parse("https://example.com/v1/proof?page=1&identity=yeiwb&platform=twitter")?;
+    let hello = request::<Hello>(call(&replay, None), Method::GET, &url, vec![]).await?;
+    assert_eq!("page 1", hello.hello);
+    assert_eq!(1, replay.remaining());
+    Ok(())
+}
+
+fn requested_hosts(transport: &FakeTransport) -> Vec<String> {
+    transport
+        .requests
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|request| request.url.host_str().unwrap().to_string())
+        .collect()
+}
+
+fn failover(
+    fake: Arc<FakeTransport>,
+    selection: Selection,
+) -> FailoverTransport<Arc<FakeTransport>> {
+    FailoverTransport::new(
+        fake,
+        vec![
+            "https://a.example.com/".into(),
+            "https://b.example.com".into(),
+        ],
+        FailoverPolicy {
+            selection,
+            failure_threshold: 2,
+            cooldown: Duration::from_secs(30),
+        },
+    )
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_failover_health() -> Result<()> {
+    let fake = Arc::new(
+        FakeTransport::sequence(vec![
+            (StatusCode::BAD_GATEWAY, "{}"),
+            (StatusCode::OK, r#"{"hello": "world"}"#),
+            (StatusCode::BAD_GATEWAY, "{}"),
+            (StatusCode::OK, r#"{"hello": "world"}"#),
+        ])
+        .with_header("content-type", "application/json"),
+    );
+    let transport = failover(fake.clone(), Selection::Priority);
+    let recorder = RecordingMetrics::default();
+    let url = url::Url::parse("https://a.example.com/v1/proof?platform=twitter")?;
+    for _ in 0..3 {
+        request::<Hello>(
+            Call {
+                metrics: Some(&recorder),
+                ..call(&transport, None)
+            },
+            Method::GET,
+            &url,
+            vec![],
+        )
+        .await?;
+    }
+    // `a` failed twice: skipped by the third request.
+    assert_eq!(
+        vec![
+            "a.example.com",
+            "b.example.com",
+            "a.example.com",
+            "b.example.com",
+            "b.example.com"
+        ],
+        requested_hosts(&fake)
+    );
+    assert_eq!(
+        Some("https://b.example.com".to_string()),
+        recorder.0.lock().unwrap()[2].backend
+    );
+    assert_eq!(
+        "/v1/proof?platform=twitter",
+        &fake.requests.lock().unwrap()[4].url[url::Position::BeforePath..]
+    );
+
+    tokio::time::advance(Duration::from_secs(31)).await;
+    request::<Hello>(call(&transport, None), Method::GET, &url, vec![]).await?;
+    assert_eq!("a.example.com", requested_hosts(&fake)[5]);
+    Ok(())
+}
+
+/// Replies to each request with the next queued result.
+struct ScriptedTransport {
+    results: Mutex<VecDeque<Result<TransportResponse>>>,
+    hosts: Mutex<Vec<String>>,
+}
+
+#[async_trait::async_trait]
+impl Transport for ScriptedTransport {
+    async fn send(&self, request: TransportRequest) -> Result<TransportResponse> {
+        let host = request.url.host_str().unwrap().to_string();
+        self.hosts.lock().unwrap().push(host);
+        self.results.lock().unwrap().pop_front().unwrap()
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_failover_health_ignores_local_errors() -> Result<()> {
+    let response = |status: StatusCode| {
+        let mut headers = HeaderMap::new();
+        headers.insert("content-type", HeaderValue::from_static("application/json"));
+        Ok(TransportResponse {
+            status,
+            headers,
+            body: br#"{"hello": "world"}"#.to_vec(),
+        })
+    };
+    let scripted = Arc::new(ScriptedTransport {
+        results: Mutex::new(VecDeque::from(vec![
+            response(StatusCode::BAD_GATEWAY),
+            response(StatusCode::OK),
+            Err(Error::BodyTooLarge(16)),
+            response(StatusCode::BAD_GATEWAY),
+            response(StatusCode::OK),
+            response(StatusCode::OK),
+        ])),
+        hosts: Mutex::new(vec![]),
+    });
+    let transport = FailoverTransport::new(
+        scripted.clone(),
+        vec![
+            "https://a.example.com".into(),
+            "https://b.example.com".into(),
+        ],
+        FailoverPolicy {
+            selection: Selection::Priority,
+            failure_threshold: 2,
+            cooldown: Duration::from_secs(30),
+        },
+    );
+    let url = url::Url::parse("https://a.example.com/v1/proof")?;
+    request::<Hello>(call(&transport, None), Method::GET, &url, vec![]).await?;
+    let result = request::<Hello>(call(&transport, None), Method::GET, &url, vec![]).await;
+    assert!(matches!(result, Err(Error::BodyTooLarge(_))));
+    request::<Hello>(call(&transport, None), Method::GET, &url, vec![]).await?;
+    // The oversized body neither counted as a failure nor reset `a`'s failures.
+    request::<Hello>(call(&transport, None), Method::GET, &url, vec![]).await?;
+    assert_eq!(
+        vec![
+            "a.example.com",
+            "b.example.com",
+            "a.example.com",
+            "a.example.com",
+            "b.example.com",
+            "b.example.com"
+        ],
+        *scripted.hosts.lock().unwrap()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_failover_round_robin_and_writes() -> Result<()> {
+    let fake = Arc::new(FakeTransport::new(StatusCode::OK, r#"{"hello": "world"}"#));
+    let transport = failover(fake.clone(), Selection::RoundRobin);
+    let url = url::Url::parse("https://b.example.com/v1/kv")?;
+    for _ in 0..3 {
+        request::<Hello>(call(&transport, None), Method::GET, &url, vec![]).await?;
+    }
+    // Not one of the backends: untouched.
+    let other = url::Url::parse("https://c.example.com/v1/kv")?;
+    request::<Hello>(call(&transport, None), Method::GET, &other, vec![]).await?;
+    assert_eq!(
+        vec![
+            "a.example.com",
+            "b.example.com",
+            "a.example.com",
+            "c.example.com"
+        ],
+        requested_hosts(&fake)
+    );
+
+    // A failed write may have been applied: no failover.
+    let fake = Arc::new(FakeTransport::new(StatusCode::BAD_GATEWAY, "{}"));
+    let transport = failover(fake.clone(), Selection::Priority);
+    let result = request::<Hello>(call(&transport, None), Method::POST, &url, b"{}".to_vec()).await;
+    assert!(result.is_err());
+    assert_eq!(1, fake.request_count());
+    Ok(())
+}